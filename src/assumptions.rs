@@ -22,6 +22,12 @@ pub struct AssumptionStore {
     rollback_boundaries: Vec<usize>,
 }
 
+impl Default for AssumptionStore {
+    fn default() -> AssumptionStore {
+        AssumptionStore::new()
+    }
+}
+
 impl AssumptionStore {
     pub fn new() -> AssumptionStore {
         AssumptionStore {
@@ -0,0 +1,169 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Backbones: the literals which are true in every model of a problem.
+//!
+//! Every literal of the first model we find is a candidate, and each model
+//! after that rules out the candidates it disagrees with (model filtering).
+//! To test candidates, we assume a chunk of their negations at once:
+//! - If that's satisfiable, none of them are in the backbone, and the model
+//!   rules out whatever else it can.
+//! - Otherwise, if the core is a single assumption, its negation is in the
+//!   backbone, and we add it as a unit, which helps the later tests. Bigger
+//!   cores mean those candidates can't all be false together, but not which
+//!   of them are in the backbone, so we drop them from the chunk and test
+//!   them one at a time later, and try the rest of the chunk again.
+//!
+//! All of this happens on one solver, which keeps what it learns.
+
+use super::cdcl;
+use super::clause as cls;
+use super::literal as lit;
+
+// How many candidates to test at once.
+const CHUNK: usize = 32;
+
+/// The backbone of `clauses`, over variables `1..=variables` and any others
+/// they mention, in order, or `None` if they are unsatisfiable.
+pub fn backbone(variables: u32, clauses: &[cls::Clause]) -> Option<Vec<lit::Literal>> {
+    let mut solver = cdcl::Solver::new();
+    for v in 1..=variables {
+        solver.add_variable(lit::Variable::new(v));
+    }
+    for c in clauses {
+        solver.add_clause(c.iter().copied());
+    }
+    if solver.solve() != cdcl::Answer::Satisfiable {
+        return None;
+    }
+
+    let mut candidates: Vec<lit::Literal> = solver.model().to_vec();
+    // Candidates which were in a bigger core, and get tested alone.
+    let mut alone: Vec<lit::Literal> = vec![];
+    let mut backbone = vec![];
+
+    loop {
+        let mut chunk: Vec<lit::Literal> = match alone.pop() {
+            Some(l) => vec![l],
+            None => candidates
+                .iter()
+                .copied()
+                .filter(|l| !alone.contains(l))
+                .take(CHUNK)
+                .collect(),
+        };
+        if chunk.is_empty() {
+            break;
+        }
+
+        while !chunk.is_empty() {
+            let assumptions: Vec<lit::Literal> = chunk.iter().map(|l| l.negate()).collect();
+            if solver.solve_with(&assumptions) == cdcl::Answer::Satisfiable {
+                // This rules out the whole chunk, and maybe more.
+                let model = solver.model();
+                candidates.retain(|l| model.binary_search(l).is_ok());
+                alone.retain(|l| model.binary_search(l).is_ok());
+                break;
+            }
+
+            match *solver.core() {
+                [negated] => {
+                    let l = negated.negate();
+                    backbone.push(l);
+                    solver.add_clause([l]);
+                    candidates.retain(|c| *c != l);
+                    chunk.retain(|c| *c != l);
+                }
+                // Only if the clauses were unsatisfiable after all.
+                [] => return None,
+                ref core => {
+                    for negated in core {
+                        let l = negated.negate();
+                        chunk.retain(|c| *c != l);
+                        if !alone.contains(&l) {
+                            alone.push(l);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    backbone.sort_unstable();
+    Some(backbone)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Random;
+
+    fn lit(l: i32) -> lit::Literal {
+        lit::Literal::from_dimacs(l).unwrap()
+    }
+
+    fn clause(ls: &[i32]) -> cls::Clause {
+        ls.iter().copied().map(lit).collect()
+    }
+
+    // The literals true in every model, by trying every assignment.
+    fn brute_force(vars: u32, clauses: &[cls::Clause]) -> Option<Vec<lit::Literal>> {
+        let models: Vec<Vec<lit::Literal>> = (0..1u64 << vars)
+            .map(|bits| {
+                (1..=vars)
+                    .map(|v| lit::Literal::new(lit::Variable::new(v), bits >> (v - 1) & 1 == 1))
+                    .collect::<Vec<_>>()
+            })
+            .filter(|m| clauses.iter().all(|c| c.iter().any(|l| m.contains(l))))
+            .collect();
+
+        let first = models.first()?;
+        Some(
+            first
+                .iter()
+                .copied()
+                .filter(|l| models.iter().all(|m| m.contains(l)))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn small() {
+        // 1 is forced, which forces -2, but 3 and 4 only have to differ.
+        let clauses = [
+            clause(&[1]),
+            clause(&[-1, -2]),
+            clause(&[3, 4]),
+            clause(&[-3, -4]),
+        ];
+        assert_eq!(backbone(5, &clauses), Some(vec![lit(1), lit(-2)]));
+
+        assert_eq!(backbone(1, &[clause(&[1]), clause(&[-1])]), None);
+        assert_eq!(backbone(3, &[]), Some(vec![]));
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = Random::new(41);
+        for _ in 0..100 {
+            let vars = 8;
+            let clauses: Vec<cls::Clause> = (0..6 + rng.below(20))
+                .map(|_| {
+                    let len = 1 + rng.below(3);
+                    (0..len)
+                        .map(|_| {
+                            let v = lit::Variable::new(1 + rng.below(vars) as u32);
+                            lit::Literal::new(v, rng.below(2) == 1)
+                        })
+                        .collect()
+                })
+                .collect();
+            assert_eq!(
+                backbone(vars as u32, &clauses),
+                brute_force(vars as u32, &clauses)
+            );
+        }
+    }
+}
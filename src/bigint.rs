@@ -0,0 +1,221 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, AddAssign, Mul};
+
+/// An arbitrarily large natural number, for results (like model counts) that
+/// don't fit in a `u64`.
+///
+/// This only has what we need, and is not fast.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct BigUint {
+    // Little-endian base 2^32 digits, with no leading zeros, so zero is empty.
+    limbs: Vec<u32>,
+}
+
+impl BigUint {
+    pub fn zero() -> BigUint {
+        BigUint { limbs: vec![] }
+    }
+
+    pub fn one() -> BigUint {
+        BigUint::from(1)
+    }
+
+    /// `2 ^ exp`
+    pub fn power_of_two(exp: usize) -> BigUint {
+        let mut limbs = vec![0; exp / 32];
+        limbs.push(1 << (exp % 32));
+        BigUint { limbs }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    /// The value, if it fits in a `u64`.
+    pub fn to_u64(&self) -> Option<u64> {
+        match self.limbs.as_slice() {
+            [] => Some(0),
+            [a] => Some(*a as u64),
+            [a, b] => Some(*a as u64 | (*b as u64) << 32),
+            _ => None,
+        }
+    }
+
+    /// Approximately, as a float. Very large values become infinity.
+    pub fn to_f64(&self) -> f64 {
+        self.limbs
+            .iter()
+            .rev()
+            .fold(0.0, |acc, l| acc * 4_294_967_296.0 + *l as f64)
+    }
+
+    fn trim(&mut self) {
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+    }
+
+    // Divide in place by a small number, returning the remainder.
+    fn div_rem_small(&mut self, divisor: u32) -> u32 {
+        let mut rem = 0u64;
+        for l in self.limbs.iter_mut().rev() {
+            let cur = rem << 32 | *l as u64;
+            *l = (cur / divisor as u64) as u32;
+            rem = cur % divisor as u64;
+        }
+        self.trim();
+        rem as u32
+    }
+}
+
+impl From<u64> for BigUint {
+    fn from(n: u64) -> BigUint {
+        let mut b = BigUint {
+            limbs: vec![n as u32, (n >> 32) as u32],
+        };
+        b.trim();
+        b
+    }
+}
+
+impl AddAssign<&BigUint> for BigUint {
+    fn add_assign(&mut self, other: &BigUint) {
+        if self.limbs.len() < other.limbs.len() {
+            self.limbs.resize(other.limbs.len(), 0);
+        }
+
+        let mut carry = 0u64;
+        for (i, l) in self.limbs.iter_mut().enumerate() {
+            let sum = *l as u64 + *other.limbs.get(i).unwrap_or(&0) as u64 + carry;
+            *l = sum as u32;
+            carry = sum >> 32;
+            if carry == 0 && i >= other.limbs.len() {
+                break;
+            }
+        }
+        if carry > 0 {
+            self.limbs.push(carry as u32);
+        }
+    }
+}
+
+impl Add<&BigUint> for &BigUint {
+    type Output = BigUint;
+
+    fn add(self, other: &BigUint) -> BigUint {
+        let mut sum = self.clone();
+        sum += other;
+        sum
+    }
+}
+
+impl Mul<&BigUint> for &BigUint {
+    type Output = BigUint;
+
+    fn mul(self, other: &BigUint) -> BigUint {
+        if self.is_zero() || other.is_zero() {
+            return BigUint::zero();
+        }
+
+        let mut limbs = vec![0u32; self.limbs.len() + other.limbs.len()];
+        for (i, a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, b) in other.limbs.iter().enumerate() {
+                let cur = limbs[i + j] as u64 + *a as u64 * *b as u64 + carry;
+                limbs[i + j] = cur as u32;
+                carry = cur >> 32;
+            }
+            limbs[i + other.limbs.len()] = carry as u32;
+        }
+
+        let mut product = BigUint { limbs };
+        product.trim();
+        product
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &BigUint) -> Ordering {
+        self.limbs
+            .len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &BigUint) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+
+        // Peel off nine decimal digits at a time.
+        let mut n = self.clone();
+        let mut chunks = vec![];
+        while !n.is_zero() {
+            chunks.push(n.div_rem_small(1_000_000_000));
+        }
+
+        write!(f, "{}", chunks.last().unwrap())?;
+        for c in chunks.iter().rev().skip(1) {
+            write!(f, "{:09}", c)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small() {
+        let a = BigUint::from(12345);
+        let b = BigUint::from(678);
+        assert_eq!((&a + &b).to_u64(), Some(13023));
+        assert_eq!((&a * &b).to_u64(), Some(8369910));
+        assert_eq!(BigUint::zero().to_string(), "0");
+        assert_eq!(BigUint::from(u64::MAX).to_u64(), Some(u64::MAX));
+        assert!(a > b);
+    }
+
+    #[test]
+    fn large() {
+        let two_64 = BigUint::power_of_two(64);
+        assert_eq!(two_64.to_string(), "18446744073709551616");
+        assert_eq!(&BigUint::from(u64::MAX) + &BigUint::one(), two_64);
+
+        let two_200 = &BigUint::power_of_two(100) * &BigUint::power_of_two(100);
+        assert_eq!(two_200, BigUint::power_of_two(200));
+        assert_eq!(
+            two_200.to_string(),
+            "1606938044258990275541962092341162602522202993782792835301376"
+        );
+        assert!(two_200 > two_64);
+        assert_eq!(two_200.to_u64(), None);
+        assert_eq!(two_200.to_f64(), 2f64.powi(200));
+    }
+
+    #[test]
+    fn carries() {
+        // (2^96 - 1) + 1 carries all the way up.
+        let mut n = BigUint {
+            limbs: vec![u32::MAX; 3],
+        };
+        n += &BigUint::one();
+        assert_eq!(n, BigUint::power_of_two(96));
+        assert_eq!(n.to_string(), "79228162514264337593543950336");
+    }
+}
@@ -0,0 +1,358 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use super::clause as c;
+use super::literal as lit;
+
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap};
+
+/// Bounded Variable Addition, as described in "Automated Reencoding of Boolean
+/// Formulas" (Manthey, Heule, Biere, 2012).
+///
+/// This looks for a set of literals `l1..ln` and a set of clauses `C1..Cm`
+/// such that every clause `li OR Cj` is in the problem. All `n * m` of these
+/// are replaced with `n + m` clauses using a fresh variable `x`:
+/// - `li OR x` for each literal, and
+/// - `~x OR Cj` for each clause.
+///
+/// We only do this when it strictly reduces the number of clauses, hence
+/// "bounded". Pairwise at-most-one constraints are the classic example of where
+/// this helps a lot.
+///
+/// Fresh variables come from `vars`, which should already have observed every
+/// variable in `clauses`. Solutions to the returned problem are solutions to
+/// the original one, once the fresh variables are dropped.
+pub fn reencode(clauses: &[c::Clause], vars: &mut lit::VariableAllocator) -> Vec<c::Clause> {
+    let mut formula = Formula::new(clauses);
+
+    // We always try the literal with the most occurrences next. Counts in the
+    // queue may be stale, so we check them again when we pop them.
+    let mut queue: BinaryHeap<(usize, lit::Literal)> = formula
+        .occurrences
+        .iter()
+        .map(|(l, occs)| (occs.len(), *l))
+        .collect();
+
+    while let Some((count, l)) = queue.pop() {
+        if count != formula.occurs(l) {
+            // Stale entry, the up-to-date one is elsewhere in the queue.
+            continue;
+        }
+
+        let (matched_lits, matched_clauses) = formula.find_replaceable(l);
+        if reduction(matched_lits.len(), matched_clauses.len()) <= 0 {
+            continue;
+        }
+
+        let x = vars.fresh();
+        let pos_x = lit::Literal::new(x, true);
+        let neg_x = pos_x.negate();
+
+        for &li in &matched_lits {
+            for &cj in &matched_clauses {
+                let mut old = formula.clause(cj).to_vec();
+                replace(&mut old, l, li);
+                formula.remove(&old);
+            }
+        }
+
+        for &li in &matched_lits {
+            formula.add(vec![li, pos_x]);
+        }
+
+        for &cj in &matched_clauses {
+            let mut rest = formula.contents[cj].clone();
+            replace(&mut rest, l, neg_x);
+            formula.add(rest);
+        }
+
+        // Occurrence counts of everything we touched have changed, so give
+        // them another go.
+        let touched: BTreeSet<lit::Literal> = matched_lits
+            .iter()
+            .copied()
+            .chain(std::iter::once(neg_x))
+            .chain(std::iter::once(pos_x))
+            .collect();
+        for t in touched {
+            queue.push((formula.occurs(t), t));
+        }
+    }
+
+    formula.into_clauses()
+}
+
+/// How many clauses we save by replacing `lits * clauses` clauses with
+/// `lits + clauses` clauses.
+fn reduction(lits: usize, clauses: usize) -> isize {
+    (lits * clauses) as isize - (lits + clauses) as isize
+}
+
+/// Replace `from` with `to` in a sorted list of literals, keeping it sorted.
+fn replace(lits: &mut Vec<lit::Literal>, from: lit::Literal, to: lit::Literal) {
+    lits.retain(|l| *l != from);
+    if let Err(idx) = lits.binary_search(&to) {
+        lits.insert(idx, to);
+    }
+}
+
+// The working copy of the problem. Clauses are kept as sorted lists of
+// literals so we can compare them cheaply, and are never removed from
+// `clauses`, only marked as deleted, so that indexes stay stable.
+struct Formula {
+    clauses: Vec<Option<Vec<lit::Literal>>>,
+    // The contents of every clause we've seen, including deleted ones, as we
+    // need the literals of a matched clause after it has been deleted.
+    contents: Vec<Vec<lit::Literal>>,
+    occurrences: BTreeMap<lit::Literal, BTreeSet<usize>>,
+    index: BTreeMap<Vec<lit::Literal>, usize>,
+}
+
+impl Formula {
+    fn new(clauses: &[c::Clause]) -> Formula {
+        let mut formula = Formula {
+            clauses: vec![],
+            contents: vec![],
+            occurrences: BTreeMap::new(),
+            index: BTreeMap::new(),
+        };
+
+        for cls in clauses {
+            let mut lits: Vec<_> = cls.iter().copied().collect();
+            lits.sort();
+            formula.add(lits);
+        }
+
+        formula
+    }
+
+    fn occurs(&self, l: lit::Literal) -> usize {
+        self.occurrences.get(&l).map_or(0, |o| o.len())
+    }
+
+    fn clause(&self, idx: usize) -> &[lit::Literal] {
+        &self.contents[idx]
+    }
+
+    fn add(&mut self, lits: Vec<lit::Literal>) {
+        // Duplicate clauses would confuse the matching below, and are useless
+        // anyway.
+        if self.index.contains_key(&lits) {
+            return;
+        }
+
+        let idx = self.clauses.len();
+        for l in &lits {
+            self.occurrences.entry(*l).or_default().insert(idx);
+        }
+        self.index.insert(lits.clone(), idx);
+        self.contents.push(lits.clone());
+        self.clauses.push(Some(lits));
+    }
+
+    fn remove(&mut self, lits: &[lit::Literal]) {
+        if let Some(idx) = self.index.remove(lits) {
+            for l in lits {
+                if let Some(occs) = self.occurrences.get_mut(l) {
+                    occs.remove(&idx);
+                }
+            }
+            self.clauses[idx] = None;
+        }
+    }
+
+    /// Find the largest (by reduction) set of literals and clauses that can be
+    /// factored out using `l`, following the SimpleBVA algorithm. Returns the
+    /// matched literals, and the indexes of the matched clauses containing `l`.
+    fn find_replaceable(&self, l: lit::Literal) -> (Vec<lit::Literal>, Vec<usize>) {
+        let mut matched_lits = vec![l];
+        let mut matched_clauses: Vec<usize> = match self.occurrences.get(&l) {
+            Some(occs) => occs.iter().copied().collect(),
+            None => return (matched_lits, vec![]),
+        };
+
+        loop {
+            // For every matched clause `C` containing `l`, find clauses that
+            // look like `C` with `l` replaced by some other literal.
+            let mut partners: BTreeMap<lit::Literal, Vec<usize>> = BTreeMap::new();
+
+            for &ci in &matched_clauses {
+                let cls = self.clause(ci);
+
+                // Any partner clause has to contain every literal other than
+                // `l`, so we only need to look at the occurrences of the
+                // rarest one.
+                let rarest = cls
+                    .iter()
+                    .copied()
+                    .filter(|other| *other != l)
+                    .min_by_key(|other| self.occurs(*other));
+                let rarest = match rarest {
+                    Some(r) => r,
+                    None => continue,
+                };
+
+                for &di in &self.occurrences[&rarest] {
+                    let other = self.clause(di);
+                    if other.len() != cls.len() || di == ci {
+                        continue;
+                    }
+
+                    if let Some(partner) = differs_only_by(cls, l, other) {
+                        if !matched_lits.contains(&partner) {
+                            partners.entry(partner).or_default().push(ci);
+                        }
+                    }
+                }
+            }
+
+            // Pick the literal that lets us keep the most clauses.
+            let best = partners
+                .into_iter()
+                .max_by_key(|(_, clauses)| clauses.len());
+
+            match best {
+                Some((partner, mut clauses))
+                    if reduction(matched_lits.len() + 1, clauses.len())
+                        > reduction(matched_lits.len(), matched_clauses.len()) =>
+                {
+                    clauses.dedup();
+                    matched_lits.push(partner);
+                    matched_clauses = clauses;
+                }
+                _ => break,
+            }
+        }
+
+        (matched_lits, matched_clauses)
+    }
+
+    fn into_clauses(self) -> Vec<c::Clause> {
+        self.clauses
+            .into_iter()
+            .flatten()
+            .map(|lits| lits.into_iter().collect())
+            .collect()
+    }
+}
+
+/// If `other` is exactly `cls` with `l` swapped for a different literal,
+/// return that literal.
+fn differs_only_by(
+    cls: &[lit::Literal],
+    l: lit::Literal,
+    other: &[lit::Literal],
+) -> Option<lit::Literal> {
+    let mut extra = other
+        .iter()
+        .copied()
+        .filter(|o| cls.binary_search(o).is_err());
+    let missing = cls
+        .iter()
+        .copied()
+        .filter(|c| other.binary_search(c).is_err());
+
+    match (extra.next(), extra.next()) {
+        (Some(partner), None) if missing.eq(std::iter::once(l)) => Some(partner),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dpll;
+
+    fn lit(l: i32) -> lit::Literal {
+        lit::Literal::from_dimacs(l).unwrap()
+    }
+
+    macro_rules! c {
+        [$($e:expr),+ $(,)?] => ({
+            [$($e),+].iter().copied().map(lit).collect::<c::Clause>()
+        })
+    }
+
+    fn allocator(clauses: &[c::Clause]) -> lit::VariableAllocator {
+        let mut vars = lit::VariableAllocator::new();
+        for cls in clauses {
+            for l in cls.iter() {
+                vars.observe(l.variable());
+            }
+        }
+        vars
+    }
+
+    // Pairwise at-most-one over variables `1..=n`, which is `n * (n - 1) / 2`
+    // clauses.
+    fn amo(n: i32) -> Vec<c::Clause> {
+        let mut clauses = vec![];
+        for i in 1..=n {
+            for j in (i + 1)..=n {
+                clauses.push(c![-i, -j]);
+            }
+        }
+        clauses
+    }
+
+    #[test]
+    fn nothing_to_do() {
+        let clauses = vec![c![1, 2], c![-1, 3]];
+        let mut vars = allocator(&clauses);
+
+        let result = reencode(&clauses, &mut vars);
+
+        assert_eq!(result.len(), 2);
+        assert_eq!(vars.last(), Some(lit::Variable::new(3)));
+    }
+
+    #[test]
+    fn grid() {
+        // (a OR c), (a OR d), (b OR c), (b OR d), (a OR e), (b OR e) becomes
+        // (a OR x), (b OR x), (~x OR c), (~x OR d), (~x OR e)
+        let clauses = vec![c![1, 3], c![1, 4], c![2, 3], c![2, 4], c![1, 5], c![2, 5]];
+        let mut vars = allocator(&clauses);
+
+        let result = reencode(&clauses, &mut vars);
+
+        assert_eq!(result.len(), 5);
+        assert_eq!(vars.last(), Some(lit::Variable::new(6)));
+    }
+
+    #[test]
+    fn at_most_one_shrinks() {
+        let clauses = amo(8);
+        let mut vars = allocator(&clauses);
+
+        let result = reencode(&clauses, &mut vars);
+
+        assert!(result.len() < clauses.len());
+        assert!(vars.last() > Some(lit::Variable::new(8)));
+    }
+
+    #[test]
+    fn preserves_satisfiability() {
+        // At most one of 1..6, and at least two of them is unsat.
+        let mut clauses = amo(6);
+        clauses.push(c![1, 2]);
+        clauses.push(c![3, 4]);
+        let mut vars = allocator(&clauses);
+        let result = reencode(&clauses, &mut vars);
+        assert!(dpll::satisfiable(&result).is_none());
+
+        // Whereas at most one, and at least one is fine.
+        let mut clauses = amo(6);
+        clauses.push(c![1, 2, 3, 4, 5, 6]);
+        let mut vars = allocator(&clauses);
+        let result = reencode(&clauses, &mut vars);
+        let soln = dpll::satisfiable(&result).unwrap();
+
+        let original: Vec<_> = soln
+            .into_iter()
+            .filter(|l| l.variable() <= lit::Variable::new(6) && l.polarity())
+            .collect();
+        assert_eq!(original.len(), 1);
+    }
+}
@@ -0,0 +1,358 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use super::assumptions as a;
+use super::cdcl;
+use super::clause as cls;
+use super::literal as lit;
+
+use std::collections::BTreeMap;
+
+/// A cardinality constraint, i.e. at least `bound` of `literals` are true.
+///
+/// At-most constraints are turned into at-least ones by negating every
+/// literal, as at most `k` of `n` literals being true is the same as at least
+/// `n - k` of them being false.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Cardinality {
+    literals: Vec<lit::Literal>,
+    bound: usize,
+}
+
+impl Cardinality {
+    pub fn at_least(literals: Vec<lit::Literal>, bound: usize) -> Cardinality {
+        Cardinality { literals, bound }
+    }
+
+    pub fn at_most(literals: Vec<lit::Literal>, bound: usize) -> Cardinality {
+        let bound = literals.len().saturating_sub(bound);
+        Cardinality {
+            literals: literals.iter().map(|l| l.negate()).collect(),
+            bound,
+        }
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, lit::Literal> {
+        self.literals.iter()
+    }
+
+    pub fn bound(&self) -> usize {
+        self.bound
+    }
+
+    /// How many literals can be false before the constraint is violated.
+    fn slack(&self) -> usize {
+        self.literals.len().saturating_sub(self.bound)
+    }
+
+    /// Is this constraint satisfied by `soln`?
+    pub fn is_satisfied(&self, soln: &[lit::Literal]) -> bool {
+        self.literals.iter().filter(|l| soln.contains(l)).count() >= self.bound
+    }
+}
+
+/// Propagates cardinality constraints by counting how many of each
+/// constraint's literals are false.
+///
+/// When that count reaches the constraint's slack, every other literal has to
+/// be true, and the reason is the literals which are already false. When it
+/// goes past the slack, those false literals are a conflict.
+pub struct CardinalityPropagator {
+    constraints: Vec<Cardinality>,
+    // For each literal, the constraints it appears in. We look these up with
+    // the negation of each assigned literal, to find what just became false.
+    occurrences: BTreeMap<lit::Literal, Vec<usize>>,
+    false_counts: Vec<usize>,
+    // How much of the trail has been counted.
+    processed: usize,
+    // Constraints which need looking at, even if nothing has changed.
+    pending: Vec<usize>,
+}
+
+impl CardinalityPropagator {
+    pub fn new(constraints: &[Cardinality]) -> CardinalityPropagator {
+        let mut occurrences: BTreeMap<lit::Literal, Vec<usize>> = BTreeMap::new();
+        for (idx, c) in constraints.iter().enumerate() {
+            for l in c.iter() {
+                occurrences.entry(*l).or_default().push(idx);
+            }
+        }
+
+        CardinalityPropagator {
+            constraints: constraints.to_vec(),
+            occurrences,
+            false_counts: vec![0; constraints.len()],
+            processed: 0,
+            pending: (0..constraints.len()).collect(),
+        }
+    }
+
+    fn check(
+        &self,
+        idx: usize,
+        trail: &cdcl::Trail,
+        implied: &mut Vec<cdcl::Reason>,
+    ) -> Result<(), cdcl::Reason> {
+        let constraint = &self.constraints[idx];
+        if constraint.bound > constraint.literals.len() {
+            // There aren't enough literals to ever satisfy it, whatever is
+            // assigned.
+            return Err(vec![]);
+        }
+
+        let slack = constraint.slack();
+        if self.false_counts[idx] < slack {
+            return Ok(());
+        }
+
+        let falsified: Vec<lit::Literal> = constraint
+            .iter()
+            .copied()
+            .filter(|l| trail.get_lit(*l) == a::Assumption::Assume(false))
+            .collect();
+
+        if falsified.len() > slack {
+            // Any `slack + 1` false literals are enough to explain this.
+            return Err(falsified.into_iter().take(slack + 1).collect());
+        }
+
+        for l in constraint.iter().copied() {
+            if trail.get_lit(l) == a::Assumption::Unknown {
+                let mut reason = vec![l];
+                reason.extend(falsified.iter().copied());
+                implied.push(reason);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl cdcl::Propagator for CardinalityPropagator {
+    fn variables(&self) -> Vec<lit::Variable> {
+        self.occurrences.keys().map(|l| l.variable()).collect()
+    }
+
+    fn propagate(&mut self, trail: &cdcl::Trail) -> Result<Vec<cdcl::Reason>, cdcl::Reason> {
+        let mut touched = std::mem::take(&mut self.pending);
+
+        for l in &trail.literals()[self.processed..] {
+            if let Some(constraints) = self.occurrences.get(&l.negate()) {
+                for idx in constraints {
+                    self.false_counts[*idx] += 1;
+                    touched.push(*idx);
+                }
+            }
+        }
+        self.processed = trail.literals().len();
+
+        touched.sort_unstable();
+        touched.dedup();
+
+        let mut implied = vec![];
+        for idx in touched {
+            self.check(idx, trail, &mut implied)?;
+        }
+
+        Ok(implied)
+    }
+
+    fn backtrack(&mut self, trail: &cdcl::Trail, len: usize) {
+        if len >= self.processed {
+            return;
+        }
+
+        for l in &trail.literals()[len..self.processed] {
+            if let Some(constraints) = self.occurrences.get(&l.negate()) {
+                for idx in constraints {
+                    self.false_counts[*idx] -= 1;
+                }
+            }
+        }
+        self.processed = len;
+    }
+}
+
+/// A totalizer, which counts how many of some literals are true, as clauses.
+///
+/// The inputs are split in half, each half is counted by a smaller totalizer,
+/// and the two counts are added together with fresh variables. Only one
+/// direction is encoded: `outputs[k]` is implied by at least `k + 1` inputs
+/// being true, so assuming it is false says at most `k` of them are. That's
+/// all an upper bound needs.
+#[derive(Debug, Clone)]
+pub struct Totalizer {
+    pub outputs: Vec<lit::Literal>,
+    pub clauses: Vec<cls::Clause>,
+}
+
+impl Totalizer {
+    /// Count `inputs`, taking any new variables from `vars`.
+    pub fn new(inputs: &[lit::Literal], vars: &mut lit::VariableAllocator) -> Totalizer {
+        let mut clauses = vec![];
+        let outputs = Totalizer::count(inputs, vars, &mut clauses);
+        Totalizer { outputs, clauses }
+    }
+
+    fn count(
+        inputs: &[lit::Literal],
+        vars: &mut lit::VariableAllocator,
+        clauses: &mut Vec<cls::Clause>,
+    ) -> Vec<lit::Literal> {
+        if inputs.len() <= 1 {
+            return inputs.to_vec();
+        }
+
+        let (left, right) = inputs.split_at(inputs.len() / 2);
+        let left = Totalizer::count(left, vars, clauses);
+        let right = Totalizer::count(right, vars, clauses);
+
+        let outputs: Vec<lit::Literal> = (0..inputs.len())
+            .map(|_| lit::Literal::new(vars.fresh(), true))
+            .collect();
+        // `i` of the left and `j` of the right make at least `i + j`.
+        for i in 0..=left.len() {
+            for j in 0..=right.len() {
+                if i + j == 0 {
+                    continue;
+                }
+                let mut clause = cls::Clause::new();
+                if i > 0 {
+                    clause.add_literal(left[i - 1].negate());
+                }
+                if j > 0 {
+                    clause.add_literal(right[j - 1].negate());
+                }
+                clause.add_literal(outputs[i + j - 1]);
+                clauses.push(clause);
+            }
+        }
+        outputs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lit(l: i32) -> lit::Literal {
+        lit::Literal::from_dimacs(l).unwrap()
+    }
+
+    fn lits(ls: &[i32]) -> Vec<lit::Literal> {
+        ls.iter().copied().map(lit).collect()
+    }
+
+    fn solve(constraints: &[Cardinality], clauses: &[&[i32]]) -> Option<Vec<lit::Literal>> {
+        let mut solver = cdcl::Solver::new();
+        for c in clauses {
+            solver.add_clause(c.iter().copied().map(lit));
+        }
+        solver.add_propagator(Box::new(CardinalityPropagator::new(constraints)));
+
+        match solver.solve() {
+            cdcl::Answer::Satisfiable => Some(solver.model().to_vec()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn at_most_negates() {
+        let c = Cardinality::at_most(lits(&[1, 2, 3, 4]), 1);
+        assert_eq!(c.bound(), 3);
+        assert_eq!(
+            c.iter().copied().collect::<Vec<_>>(),
+            lits(&[-1, -2, -3, -4])
+        );
+    }
+
+    #[test]
+    fn at_least_propagates() {
+        let constraints = vec![Cardinality::at_least(lits(&[1, 2, 3]), 2)];
+        let soln = solve(&constraints, &[&[-1]]).unwrap();
+
+        assert!(soln.contains(&lit(2)));
+        assert!(soln.contains(&lit(3)));
+    }
+
+    #[test]
+    fn at_most_propagates() {
+        let constraints = vec![Cardinality::at_most(lits(&[1, 2, 3, 4]), 2)];
+        let soln = solve(&constraints, &[&[1], &[2]]).unwrap();
+
+        assert!(soln.contains(&lit(-3)));
+        assert!(soln.contains(&lit(-4)));
+    }
+
+    #[test]
+    fn impossible_bound() {
+        let constraints = vec![Cardinality::at_least(lits(&[1, 2]), 3)];
+        assert!(solve(&constraints, &[]).is_none());
+
+        // No assumption is to blame, as nothing could satisfy it.
+        let mut solver = cdcl::Solver::new();
+        solver.add_propagator(Box::new(CardinalityPropagator::new(&constraints)));
+        assert_eq!(solver.solve_with(&[lit(-1)]), cdcl::Answer::Unsatisfiable);
+        assert!(solver.core().is_empty());
+    }
+
+    #[test]
+    fn pigeonhole() {
+        // 6 pigeons, 5 holes: each pigeon in at least one hole, and each hole
+        // has at most one pigeon. This is famously hard for clauses alone.
+        let var = |p: i32, h: i32| p * 5 + h + 1;
+        let mut constraints = vec![];
+        for p in 0..6 {
+            constraints.push(Cardinality::at_least(
+                (0..5).map(|h| lit(var(p, h))).collect(),
+                1,
+            ));
+        }
+        for h in 0..5 {
+            constraints.push(Cardinality::at_most(
+                (0..6).map(|p| lit(var(p, h))).collect(),
+                1,
+            ));
+        }
+        assert!(solve(&constraints, &[]).is_none());
+
+        // Without the last pigeon it fits.
+        constraints.remove(5);
+        let soln = solve(&constraints, &[]).unwrap();
+        assert!(constraints.iter().all(|c| c.is_satisfied(&soln)));
+    }
+
+    #[test]
+    fn totalizer() {
+        let mut vars = lit::VariableAllocator::new();
+        vars.observe(lit::Variable::new(5));
+        let t = Totalizer::new(&lits(&[1, 2, 3, 4, 5]), &mut vars);
+        assert_eq!(t.outputs.len(), 5);
+        let clauses: Vec<Vec<i32>> = t
+            .clauses
+            .iter()
+            .map(|c| c.iter().map(|l| l.to_dimacs().parse().unwrap()).collect())
+            .collect();
+        let clauses: Vec<&[i32]> = clauses.iter().map(|c| c.as_slice()).collect();
+
+        // With three inputs true, the third output has to be.
+        let mut with_three = clauses.clone();
+        with_three.extend([&[1][..], &[3], &[5]]);
+        let soln = solve(&[], &with_three).unwrap();
+        assert!(soln.contains(&t.outputs[2]));
+
+        // But it needn't be with two.
+        let negated = t.outputs[2].negate().to_dimacs().parse::<i32>().unwrap();
+        let mut with_two = clauses.clone();
+        with_two.extend([&[1][..], &[3], &[-2], &[-4], &[-5]]);
+        let not_third = [negated];
+        with_two.push(&not_third);
+        assert!(solve(&[], &with_two).is_some());
+
+        // And with three it can't be false.
+        with_three.push(&not_third);
+        assert!(solve(&[], &with_three).is_none());
+    }
+}
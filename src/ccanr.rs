@@ -0,0 +1,228 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use super::clause as cls;
+use super::local_search::LocalSearch;
+use super::random::Random;
+use super::walksat::{State, Walk};
+
+type Problem<'a> = &'a [cls::Clause];
+
+/// Configuration checking with clause weighting, in the style of CCAnr from
+/// "CCAnr: A Configuration Checking Based Local Search Solver for Non-random
+/// Satisfiability" (Cai, Luo, Su, 2015).
+///
+/// Every clause has a weight, and a variable's score is the weight of the
+/// clauses flipping it would satisfy, less the weight of those it would
+/// break. Each step, we flip:
+/// - The best variable with a positive score whose neighbourhood (the
+///   variables it shares a clause with) has changed since it was last
+///   flipped. This stops us immediately undoing our own flips.
+/// - Otherwise, the best variable whose score is above the average clause
+///   weight, even if its neighbourhood hasn't changed.
+/// - Otherwise we are stuck, so we increase the weight of every unsatisfied
+///   clause, and flip the best variable in a random one of them.
+///
+/// Ties are broken in favour of the variable flipped longest ago. When the
+/// average weight passes `threshold`, all the weights are smoothed towards
+/// it, so old weights are forgotten.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// How many variables to flip before giving up.
+    pub max_flips: u64,
+    /// The average clause weight at which we smooth the weights.
+    pub threshold: u64,
+    /// How much of its own weight each clause keeps when smoothing, with the
+    /// rest coming from the average.
+    pub rho: f64,
+    pub seed: u64,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            max_flips: 1_000_000,
+            threshold: 50,
+            rho: 0.3,
+            seed: 0,
+        }
+    }
+}
+
+impl LocalSearch for Config {
+    fn search(&self, clauses: Problem) -> Walk {
+        let mut rng = Random::new(self.seed);
+        let mut state = State::new(clauses, &mut rng);
+        let neighbours = neighbours(&state);
+
+        let mut changed = vec![true; state.variables() + 1];
+        let mut flipped_at = vec![0; state.variables() + 1];
+        let mut total_weight = state.clauses.len() as u64;
+
+        let mut best = state.assignment();
+        let mut best_unsatisfied = state.unsatisfied.len();
+        let mut flips = 0;
+
+        while !state.unsatisfied.is_empty() && flips < self.max_flips {
+            if state
+                .unsatisfied
+                .iter()
+                .any(|c| state.clauses[*c].is_empty())
+            {
+                // The empty clause, which no amount of flipping will fix.
+                break;
+            }
+
+            // Only variables in unsatisfied clauses can have a positive score.
+            let candidates: Vec<usize> = state
+                .unsatisfied
+                .iter()
+                .flat_map(|c| state.clause_vars(*c))
+                .collect();
+            let best_of = |pick: &dyn Fn(usize) -> bool| {
+                candidates
+                    .iter()
+                    .copied()
+                    .filter(|v| pick(*v))
+                    .max_by_key(|v| (state.score(*v), std::cmp::Reverse(flipped_at[*v])))
+            };
+
+            let average = total_weight / state.clauses.len() as u64;
+            let var = if let Some(v) = best_of(&|v| changed[v] && state.score(v) > 0) {
+                v
+            } else if let Some(v) = best_of(&|v| state.score(v) > average as i64) {
+                v
+            } else {
+                for c in state.unsatisfied.clone() {
+                    state.set_weight(c, state.weights[c] + 1);
+                }
+                total_weight += state.unsatisfied.len() as u64;
+
+                if total_weight / state.clauses.len() as u64 > self.threshold {
+                    total_weight = self.smooth(&mut state);
+                }
+
+                let clause = state.unsatisfied[rng.below(state.unsatisfied.len())];
+                state
+                    .clause_vars(clause)
+                    .into_iter()
+                    .max_by_key(|v| (state.score(*v), std::cmp::Reverse(flipped_at[*v])))
+                    .unwrap()
+            };
+
+            state.flip(var);
+            flips += 1;
+            flipped_at[var] = flips;
+            changed[var] = false;
+            for n in &neighbours[var] {
+                changed[*n] = true;
+            }
+
+            if state.unsatisfied.len() < best_unsatisfied {
+                best_unsatisfied = state.unsatisfied.len();
+                best = state.assignment();
+            }
+        }
+
+        Walk {
+            assignment: best,
+            unsatisfied: best_unsatisfied,
+            flips,
+        }
+    }
+}
+
+impl Config {
+    // Move every weight towards the average, returning the new total.
+    fn smooth(&self, state: &mut State) -> u64 {
+        let average = state.weights.iter().sum::<u64>() as f64 / state.weights.len() as f64;
+        let mut total = 0;
+        for c in 0..state.weights.len() {
+            let weight = (self.rho * state.weights[c] as f64 + (1.0 - self.rho) * average) as u64;
+            state.set_weight(c, weight.max(1));
+            total += weight.max(1);
+        }
+        total
+    }
+}
+
+// For each variable, the other variables it shares a clause with.
+fn neighbours(state: &State) -> Vec<Vec<usize>> {
+    let mut occurrences = vec![vec![]; state.variables() + 1];
+    for c in 0..state.clauses.len() {
+        for v in state.clause_vars(c) {
+            occurrences[v].push(c);
+        }
+    }
+
+    let mut seen = vec![usize::MAX; state.variables() + 1];
+    let mut neighbours = vec![vec![]; state.variables() + 1];
+    for (v, cs) in occurrences.iter().enumerate() {
+        seen[v] = v;
+        for c in cs {
+            for n in state.clause_vars(*c) {
+                if seen[n] != v {
+                    seen[n] = v;
+                    neighbours[v].push(n);
+                }
+            }
+        }
+    }
+    neighbours
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::literal as lit;
+    use crate::local_search::tests::{planted, satisfies};
+
+    fn lit(l: i32) -> lit::Literal {
+        lit::Literal::from_dimacs(l).unwrap()
+    }
+
+    #[test]
+    fn random_3sat() {
+        let clauses = planted(200, 840, 3, 6);
+        let walk = Config::default().search(&clauses);
+
+        assert!(walk.is_solution());
+        assert!(satisfies(&walk.assignment, &clauses));
+    }
+
+    #[test]
+    fn random_5sat() {
+        let clauses = planted(100, 1800, 5, 7);
+        let config = Config {
+            seed: 3,
+            ..Config::default()
+        };
+        let walk = config.search(&clauses);
+
+        assert!(walk.is_solution());
+        assert!(satisfies(&walk.assignment, &clauses));
+    }
+
+    #[test]
+    fn gives_up() {
+        // Unsatisfiable, so the weights keep growing, and get smoothed many
+        // times.
+        let clauses: Vec<cls::Clause> = vec![
+            [1, 2].iter().copied().map(lit).collect(),
+            [-1, 2].iter().copied().map(lit).collect(),
+            [1, -2].iter().copied().map(lit).collect(),
+            [-1, -2].iter().copied().map(lit).collect(),
+        ];
+        let config = Config {
+            max_flips: 10_000,
+            ..Config::default()
+        };
+        let walk = config.search(&clauses);
+
+        assert!(!walk.is_solution());
+        assert_eq!(walk.unsatisfied, 1);
+        assert_eq!(walk.flips, 10_000);
+    }
+}
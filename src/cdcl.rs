@@ -0,0 +1,1233 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! A Conflict-Driven Clause Learning solver.
+//!
+//! When an assignment falsifies a clause, the solver works out which earlier
+//! decisions were to blame, learns a clause that rules them out, and
+//! backjumps to where that clause says something new. Clauses are watched by
+//! two of their literals, so an assignment only visits the clauses watching
+//! its negation, rather than every clause.
+//!
+//! Constraints that aren't clauses (XORs, cardinality constraints, etc) are
+//! plugged in as `Propagator`s, which have to explain everything they infer
+//! with a clause so that conflict analysis can treat them like any other
+//! clause.
+
+use super::assumptions as a;
+use super::literal as lit;
+use super::random::Random;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A clause justifying an inference, where the first literal is the one being
+/// inferred, and all the others are false under the current assignment.
+pub type Reason = Vec<lit::Literal>;
+
+/// The current partial assignment, as seen by a `Propagator`.
+///
+/// Literals are assigned in order, grouped into decision levels. Level 0 holds
+/// everything that is true regardless of any decisions.
+#[derive(Debug, Default)]
+pub struct Trail {
+    values: Vec<a::Assumption>,
+    levels: Vec<usize>,
+    literals: Vec<lit::Literal>,
+    // Where each decision level starts in `literals`.
+    limits: Vec<usize>,
+}
+
+impl Trail {
+    pub fn get_var(&self, var: lit::Variable) -> a::Assumption {
+        self.values
+            .get(var.index() as usize)
+            .copied()
+            .unwrap_or(a::Assumption::Unknown)
+    }
+
+    // Get the assumption for a literal. This respects the polarity of the literal,
+    // if an assumption is present.
+    pub fn get_lit(&self, l: lit::Literal) -> a::Assumption {
+        match self.get_var(l.variable()) {
+            a::Assumption::Assume(b) => a::Assumption::Assume(b == l.polarity()),
+            a::Assumption::Unknown => a::Assumption::Unknown,
+        }
+    }
+
+    /// Every assigned literal, in the order they were assigned.
+    pub fn literals(&self) -> &[lit::Literal] {
+        &self.literals
+    }
+
+    /// The decision level `var` was assigned at. Meaningless if `var` is not
+    /// assigned.
+    pub fn level(&self, var: lit::Variable) -> usize {
+        self.levels[var.index() as usize]
+    }
+
+    pub fn decision_level(&self) -> usize {
+        self.limits.len()
+    }
+
+    fn is_true(&self, l: lit::Literal) -> bool {
+        self.get_lit(l) == a::Assumption::Assume(true)
+    }
+
+    fn is_false(&self, l: lit::Literal) -> bool {
+        self.get_lit(l) == a::Assumption::Assume(false)
+    }
+}
+
+/// A constraint that isn't a clause, which can take part in the search.
+pub trait Propagator: Send {
+    /// Every variable this constraint mentions. The solver makes sure they all
+    /// get assigned before it reports a solution.
+    fn variables(&self) -> Vec<lit::Variable>;
+
+    /// Look at the assignment in `trail`, and either return everything this
+    /// constraint implies, each with a `Reason`, or a clause that is entirely
+    /// false if the constraint is violated.
+    ///
+    /// It is fine to return implications which are already true.
+    fn propagate(&mut self, trail: &Trail) -> Result<Vec<Reason>, Reason>;
+
+    /// The solver is about to undo every assignment in `trail.literals()` from
+    /// index `len` onwards.
+    fn backtrack(&mut self, trail: &Trail, len: usize);
+}
+
+/// A way to swap learnt clauses with other solvers working on the same
+/// problem.
+///
+/// Every solver sharing clauses must have the same clauses and propagators,
+/// as what one learns might not follow from what another knows.
+pub trait Exchange: Send {
+    /// The solver has learnt `lits`, whose literals are in `lbd` different
+    /// decision levels.
+    fn export(&mut self, lits: &[lit::Literal], lbd: usize);
+
+    /// Clauses the solver should learn from the others. This is called at
+    /// every restart.
+    fn import(&mut self) -> Vec<Vec<lit::Literal>>;
+}
+
+/// The result of asking the solver about a problem.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Answer {
+    Satisfiable,
+    Unsatisfiable,
+    /// The solver gave up before finding out.
+    Unknown,
+}
+
+/// When the solver gives up on its current decisions and starts again, keeping
+/// what it has learnt. The sequences are in numbers of conflicts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Restarts {
+    /// `unit` times the Luby sequence: 1, 1, 2, 1, 1, 2, 4, ...
+    Luby { unit: u64 },
+    /// `first`, then each restart `factor` times longer than the last.
+    Geometric { first: u64, factor: f64 },
+}
+
+impl Restarts {
+    fn budget(&self, restarts: u64) -> u64 {
+        match *self {
+            Restarts::Luby { unit } => luby(restarts) * unit,
+            Restarts::Geometric { first, factor } => {
+                (first as f64 * factor.powi(restarts.min(i32::MAX as u64) as i32)) as u64
+            }
+        }
+    }
+}
+
+/// How the solver should behave. Solvers with different configurations find
+/// different solutions, at different speeds.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Seeds random decisions, and if it isn't zero, shuffles the order
+    /// variables are first decided in.
+    pub seed: u64,
+    pub restarts: Restarts,
+    /// How quickly variable activity is forgotten: the closer to 1, the more
+    /// past conflicts matter.
+    pub var_decay: f64,
+    /// The probability of deciding on a random variable, rather than the most
+    /// active one.
+    pub random_decisions: f64,
+    /// The value decided for a variable that has never been assigned.
+    pub initial_phase: bool,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            seed: 0,
+            restarts: Restarts::Luby { unit: 100 },
+            var_decay: 0.95,
+            random_decisions: 0.0,
+            initial_phase: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Statistics {
+    pub decisions: u64,
+    pub propagations: u64,
+    pub conflicts: u64,
+    pub restarts: u64,
+    /// Clauses taken from an `Exchange`.
+    pub imported: u64,
+}
+
+// Why a variable was assigned. Decisions (including assumptions), and facts at
+// level 0 have no reason.
+#[derive(Debug)]
+enum Implied {
+    Clause(usize),
+    External(Reason),
+}
+
+#[derive(Debug)]
+struct StoredClause {
+    lits: Vec<lit::Literal>,
+    learnt: bool,
+    lbd: usize,
+    deleted: bool,
+}
+
+/// A CDCL solver, which can be used incrementally: clauses can be added
+/// between calls to `solve`, and each call can be given a set of assumptions.
+pub struct Solver {
+    trail: Trail,
+    reasons: Vec<Option<Implied>>,
+    qhead: usize,
+    clauses: Vec<StoredClause>,
+    // Indexed by `code`. The clauses watching a literal, to be looked at when
+    // that literal becomes false.
+    watches: Vec<Vec<usize>>,
+    propagators: Vec<Box<dyn Propagator>>,
+    order: VarOrder,
+    // Phase saving: the polarity each variable last had.
+    phases: Vec<bool>,
+    seen: Vec<bool>,
+    ok: bool,
+    model: Vec<lit::Literal>,
+    core: Vec<lit::Literal>,
+    next_reduce: u64,
+    stats: Statistics,
+    config: Config,
+    rng: Random,
+    interrupt: Option<Arc<AtomicBool>>,
+    exchange: Option<Box<dyn Exchange>>,
+}
+
+impl Default for Solver {
+    fn default() -> Solver {
+        Solver::new()
+    }
+}
+
+impl Solver {
+    pub fn new() -> Solver {
+        Solver::with_config(Config::default())
+    }
+
+    pub fn with_config(config: Config) -> Solver {
+        Solver {
+            trail: Trail::default(),
+            reasons: vec![],
+            qhead: 0,
+            clauses: vec![],
+            watches: vec![],
+            propagators: vec![],
+            order: VarOrder::new(config.var_decay),
+            phases: vec![],
+            seen: vec![],
+            ok: true,
+            model: vec![],
+            core: vec![],
+            next_reduce: 2000,
+            stats: Statistics::default(),
+            rng: Random::new(config.seed),
+            config,
+            interrupt: None,
+            exchange: None,
+        }
+    }
+
+    /// Make sure the solver knows about `var`, so it will be assigned in any
+    /// solution, even if it's not in any clause.
+    pub fn add_variable(&mut self, var: lit::Variable) {
+        let idx = var.index() as usize;
+        if idx < self.trail.values.len() {
+            return;
+        }
+
+        for v in self.trail.values.len().max(1)..=idx {
+            self.trail.values.resize(v + 1, a::Assumption::Unknown);
+            self.trail.levels.resize(v + 1, 0);
+            self.reasons.resize_with(v + 1, || None);
+            self.phases.resize(v + 1, self.config.initial_phase);
+            self.seen.resize(v + 1, false);
+            self.watches.resize_with(2 * (v + 1), Vec::new);
+            if self.config.seed != 0 {
+                // Too small to matter after the first few conflicts.
+                self.order.activity.resize(v + 1, 0.0);
+                self.order.activity[v] = self.rng.fraction() * 1e-5;
+            }
+            self.order.insert(v as u32);
+        }
+    }
+
+    /// Suggest a value for a variable, which the solver will try first when it
+    /// has to guess. It will remember different values later, as it learns.
+    pub fn set_phase(&mut self, l: lit::Literal) {
+        self.add_variable(l.variable());
+        self.phases[l.variable().index() as usize] = l.polarity();
+    }
+
+    /// The number of variables, i.e. the largest variable the solver has seen.
+    pub fn variables(&self) -> usize {
+        self.trail.values.len().saturating_sub(1)
+    }
+
+    pub fn statistics(&self) -> Statistics {
+        self.stats
+    }
+
+    /// Give the solver a flag which, when set, makes `solve` give up and
+    /// return `Unknown` as soon as it notices. The solver never clears it.
+    pub fn set_interrupt(&mut self, flag: Arc<AtomicBool>) {
+        self.interrupt = Some(flag);
+    }
+
+    /// Share learnt clauses through `exchange`.
+    pub fn set_exchange(&mut self, exchange: Box<dyn Exchange>) {
+        self.exchange = Some(exchange);
+    }
+
+    fn interrupted(&self) -> bool {
+        self.interrupt
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::Relaxed))
+    }
+
+    /// Add a clause to the problem. Returns `false` if the problem is now
+    /// known to be unsatisfiable.
+    pub fn add_clause<I: IntoIterator<Item = lit::Literal>>(&mut self, lits: I) -> bool {
+        if !self.ok {
+            return false;
+        }
+        self.cancel_until(0);
+
+        let mut clause: Vec<lit::Literal> = vec![];
+        for l in lits {
+            self.add_variable(l.variable());
+            if self.trail.is_true(l) || clause.contains(&l.negate()) {
+                // Satisfied, or a tautology, either way we don't need it.
+                return true;
+            }
+            if !self.trail.is_false(l) && !clause.contains(&l) {
+                clause.push(l);
+            }
+        }
+
+        match clause.len() {
+            0 => self.ok = false,
+            1 => {
+                self.enqueue(clause[0], None);
+                self.ok = self.propagate().is_none();
+            }
+            _ => {
+                self.attach(clause, false, 0);
+            }
+        }
+
+        self.ok
+    }
+
+    /// Add a constraint that isn't a clause to the problem.
+    pub fn add_propagator(&mut self, propagator: Box<dyn Propagator>) {
+        self.cancel_until(0);
+        for var in propagator.variables() {
+            self.add_variable(var);
+        }
+        self.propagators.push(propagator);
+    }
+
+    pub fn solve(&mut self) -> Answer {
+        self.solve_with(&[])
+    }
+
+    /// Solve the problem, with every literal in `assumptions` taken to be
+    /// true for this call only. If the answer is `Unsatisfiable`, `core` says
+    /// which of the assumptions were to blame.
+    pub fn solve_with(&mut self, assumptions: &[lit::Literal]) -> Answer {
+        self.model.clear();
+        self.core.clear();
+
+        if !self.ok {
+            return Answer::Unsatisfiable;
+        }
+
+        for l in assumptions {
+            self.add_variable(l.variable());
+        }
+
+        let mut restarts = 0;
+        loop {
+            let budget = self.config.restarts.budget(restarts);
+            if let Some(answer) = self.search(budget, assumptions) {
+                self.cancel_until(0);
+                return answer;
+            }
+            restarts += 1;
+            self.stats.restarts += 1;
+
+            if !self.import() {
+                return Answer::Unsatisfiable;
+            }
+        }
+    }
+
+    // Learn the clauses other solvers have shared. We are at level 0, so they
+    // are simplified and added like any other clause. Returns `false` if the
+    // problem is now known to be unsatisfiable.
+    fn import(&mut self) -> bool {
+        let imported = match self.exchange.as_mut() {
+            Some(exchange) => exchange.import(),
+            None => return true,
+        };
+
+        for lits in imported {
+            self.stats.imported += 1;
+
+            let mut clause = vec![];
+            let mut satisfied = false;
+            for l in lits {
+                self.add_variable(l.variable());
+                satisfied |= self.trail.is_true(l);
+                if !self.trail.is_false(l) && !clause.contains(&l) {
+                    clause.push(l);
+                }
+            }
+
+            if satisfied {
+                continue;
+            }
+            match clause.len() {
+                0 => {
+                    self.ok = false;
+                    return false;
+                }
+                1 => {
+                    self.enqueue(clause[0], None);
+                    if self.propagate().is_some() {
+                        self.ok = false;
+                        return false;
+                    }
+                }
+                _ => {
+                    let lbd = clause.len();
+                    self.attach(clause, true, lbd);
+                }
+            }
+        }
+        true
+    }
+
+    /// The solution found by the last call to `solve`, if it was satisfiable.
+    /// Contains a literal for every variable the solver knows about.
+    pub fn model(&self) -> &[lit::Literal] {
+        &self.model
+    }
+
+    /// If the last call to `solve_with` was unsatisfiable, the subset of the
+    /// assumptions that caused it. This is empty if the problem is
+    /// unsatisfiable without any assumptions.
+    pub fn core(&self) -> &[lit::Literal] {
+        &self.core
+    }
+
+    // Returns `None` if it runs out of conflicts before finding an answer,
+    // which means it's time to restart.
+    fn search(&mut self, budget: u64, assumptions: &[lit::Literal]) -> Option<Answer> {
+        let mut conflicts = 0;
+
+        loop {
+            if self.interrupted() {
+                self.cancel_until(0);
+                return Some(Answer::Unknown);
+            }
+
+            if let Some(conflict) = self.propagate() {
+                conflicts += 1;
+                self.stats.conflicts += 1;
+
+                // Propagators may find conflicts which don't involve the
+                // current level, so analyze them where they happened.
+                let level = conflict
+                    .iter()
+                    .map(|l| self.trail.level(l.variable()))
+                    .max()
+                    .unwrap_or(0);
+                if level == 0 {
+                    self.ok = false;
+                    return Some(Answer::Unsatisfiable);
+                }
+                self.cancel_until(level);
+
+                let (learnt, level) = self.analyze(conflict);
+                self.cancel_until(level);
+
+                if learnt.len() == 1 {
+                    if let Some(exchange) = self.exchange.as_mut() {
+                        exchange.export(&learnt, 1);
+                    }
+                    self.enqueue(learnt[0], None);
+                } else {
+                    let lbd = self.lbd(&learnt);
+                    if let Some(exchange) = self.exchange.as_mut() {
+                        exchange.export(&learnt, lbd);
+                    }
+                    let first = learnt[0];
+                    let idx = self.attach(learnt, true, lbd);
+                    self.enqueue(first, Some(Implied::Clause(idx)));
+                }
+
+                self.order.decay();
+
+                if self.stats.conflicts >= self.next_reduce {
+                    self.next_reduce = self.stats.conflicts + 2000 + 300 * self.stats.restarts;
+                    self.reduce_learnts();
+                }
+            } else {
+                if conflicts >= budget {
+                    self.cancel_until(0);
+                    return None;
+                }
+
+                let mut next = None;
+                while self.trail.decision_level() < assumptions.len() {
+                    let p = assumptions[self.trail.decision_level()];
+                    match self.trail.get_lit(p) {
+                        // Already true, but we still need a level for it so
+                        // that the levels line up with the assumptions.
+                        a::Assumption::Assume(true) => self.new_decision_level(),
+                        a::Assumption::Assume(false) => {
+                            self.analyze_final(p);
+                            return Some(Answer::Unsatisfiable);
+                        }
+                        a::Assumption::Unknown => {
+                            next = Some(p);
+                            break;
+                        }
+                    }
+                }
+
+                let next = match next.or_else(|| self.pick_branch()) {
+                    Some(l) => l,
+                    None => {
+                        // Every variable is assigned, and nothing is violated.
+                        self.model = self.trail.literals.clone();
+                        self.model.sort();
+                        return Some(Answer::Satisfiable);
+                    }
+                };
+
+                self.stats.decisions += 1;
+                self.new_decision_level();
+                self.enqueue(next, None);
+            }
+        }
+    }
+
+    fn new_decision_level(&mut self) {
+        self.trail.limits.push(self.trail.literals.len());
+    }
+
+    fn enqueue(&mut self, l: lit::Literal, reason: Option<Implied>) {
+        let v = l.variable().index() as usize;
+        self.trail.values[v] = a::Assumption::Assume(l.polarity());
+        self.trail.levels[v] = self.trail.decision_level();
+        self.reasons[v] = reason;
+        self.trail.literals.push(l);
+    }
+
+    fn cancel_until(&mut self, level: usize) {
+        if self.trail.decision_level() <= level {
+            return;
+        }
+
+        let len = self.trail.limits[level];
+        for p in self.propagators.iter_mut() {
+            p.backtrack(&self.trail, len);
+        }
+
+        for l in self.trail.literals.drain(len..) {
+            let v = l.variable().index() as usize;
+            self.trail.values[v] = a::Assumption::Unknown;
+            self.reasons[v] = None;
+            self.phases[v] = l.polarity();
+            self.order.insert(v as u32);
+        }
+
+        self.trail.limits.truncate(level);
+        self.qhead = self.qhead.min(len);
+    }
+
+    fn attach(&mut self, lits: Vec<lit::Literal>, learnt: bool, lbd: usize) -> usize {
+        let idx = self.clauses.len();
+        self.watches[lits[0].code()].push(idx);
+        self.watches[lits[1].code()].push(idx);
+        self.clauses.push(StoredClause {
+            lits,
+            learnt,
+            lbd,
+            deleted: false,
+        });
+        idx
+    }
+
+    // Unit propagation, followed by asking every propagator, until nothing
+    // changes. Returns a clause which is false if there is a conflict.
+    fn propagate(&mut self) -> Option<Reason> {
+        loop {
+            if let Some(conflict) = self.propagate_clauses() {
+                return Some(conflict);
+            }
+
+            let mut progress = false;
+            for i in 0..self.propagators.len() {
+                let implied = match self.propagators[i].propagate(&self.trail) {
+                    Ok(implied) => implied,
+                    Err(conflict) => return Some(conflict),
+                };
+
+                for reason in implied {
+                    match self.trail.get_lit(reason[0]) {
+                        a::Assumption::Assume(true) => (),
+                        a::Assumption::Assume(false) => return Some(reason),
+                        a::Assumption::Unknown => {
+                            self.enqueue(reason[0], Some(Implied::External(reason)));
+                            progress = true;
+                        }
+                    }
+                }
+            }
+
+            if !progress {
+                return None;
+            }
+        }
+    }
+
+    fn propagate_clauses(&mut self) -> Option<Reason> {
+        while self.qhead < self.trail.literals.len() {
+            let false_lit = self.trail.literals[self.qhead].negate();
+            self.qhead += 1;
+            self.stats.propagations += 1;
+
+            let mut watchers = std::mem::take(&mut self.watches[false_lit.code()]);
+            let mut kept = 0;
+            let mut conflict = None;
+
+            let mut i = 0;
+            while i < watchers.len() {
+                let ci = watchers[i];
+                i += 1;
+
+                let clause = &mut self.clauses[ci];
+                if clause.deleted {
+                    // Lazily forget about deleted clauses.
+                    continue;
+                }
+
+                // Make sure the false literal is the second one.
+                if clause.lits[0] == false_lit {
+                    clause.lits.swap(0, 1);
+                }
+
+                let first = clause.lits[0];
+                if self.trail.is_true(first) {
+                    watchers[kept] = ci;
+                    kept += 1;
+                    continue;
+                }
+
+                // Look for another literal to watch instead.
+                let trail = &self.trail;
+                let replacement = (2..clause.lits.len()).find(|k| !trail.is_false(clause.lits[*k]));
+                if let Some(k) = replacement {
+                    clause.lits.swap(1, k);
+                    self.watches[clause.lits[1].code()].push(ci);
+                    continue;
+                }
+
+                watchers[kept] = ci;
+                kept += 1;
+
+                if self.trail.is_false(first) {
+                    conflict = Some(clause.lits.clone());
+                    break;
+                }
+                self.enqueue(first, Some(Implied::Clause(ci)));
+            }
+
+            // Keep any watchers we didn't get to because of a conflict.
+            while i < watchers.len() {
+                watchers[kept] = watchers[i];
+                kept += 1;
+                i += 1;
+            }
+            watchers.truncate(kept);
+            self.watches[false_lit.code()] = watchers;
+
+            if conflict.is_some() {
+                return conflict;
+            }
+        }
+
+        None
+    }
+
+    fn reason_lits(&self, var: lit::Variable) -> &[lit::Literal] {
+        match &self.reasons[var.index() as usize] {
+            Some(Implied::Clause(ci)) => &self.clauses[*ci].lits,
+            Some(Implied::External(reason)) => reason,
+            None => &[],
+        }
+    }
+
+    // First-UIP conflict analysis. Returns the learnt clause, with the
+    // asserting literal first, and the level to backjump to.
+    fn analyze(&mut self, conflict: Reason) -> (Vec<lit::Literal>, usize) {
+        let current = self.trail.decision_level();
+        let mut learnt = vec![];
+        let mut pending = 0;
+        let mut index = self.trail.literals.len();
+        let mut uip: Option<lit::Literal> = None;
+
+        let mut clause = conflict;
+
+        loop {
+            for q in clause.iter().copied() {
+                let v = q.variable();
+                if Some(v) == uip.map(|u| u.variable()) {
+                    continue;
+                }
+
+                let vi = v.index() as usize;
+                if !self.seen[vi] && self.trail.level(v) > 0 {
+                    self.seen[vi] = true;
+                    self.order.bump(vi as u32);
+                    if self.trail.level(v) >= current {
+                        pending += 1;
+                    } else {
+                        learnt.push(q);
+                    }
+                }
+            }
+
+            // Find the next literal on the trail we need to look at.
+            loop {
+                index -= 1;
+                if self.seen[self.trail.literals[index].variable().index() as usize] {
+                    break;
+                }
+            }
+
+            let p = self.trail.literals[index];
+            self.seen[p.variable().index() as usize] = false;
+            uip = Some(p);
+            pending -= 1;
+
+            if pending == 0 {
+                break;
+            }
+
+            clause = self.reason_lits(p.variable()).to_vec();
+        }
+
+        for l in &learnt {
+            self.seen[l.variable().index() as usize] = false;
+        }
+
+        learnt.insert(0, uip.unwrap().negate());
+
+        // Backjump to the second highest level in the clause, and make sure
+        // that literal is watched.
+        let mut level = 0;
+        if learnt.len() > 1 {
+            let mut max = 1;
+            for i in 2..learnt.len() {
+                if self.trail.level(learnt[i].variable()) > self.trail.level(learnt[max].variable())
+                {
+                    max = i;
+                }
+            }
+            learnt.swap(1, max);
+            level = self.trail.level(learnt[1].variable());
+        }
+
+        (learnt, level)
+    }
+
+    // `p` is an assumption which is false. Work out which assumptions caused
+    // that, and store them in `core`.
+    fn analyze_final(&mut self, p: lit::Literal) {
+        self.core.clear();
+        self.core.push(p);
+
+        if self.trail.decision_level() == 0 {
+            return;
+        }
+
+        self.seen[p.variable().index() as usize] = true;
+
+        for i in (self.trail.limits[0]..self.trail.literals.len()).rev() {
+            let l = self.trail.literals[i];
+            let v = l.variable().index() as usize;
+            if !self.seen[v] {
+                continue;
+            }
+
+            if self.reasons[v].is_none() {
+                // Only assumptions are decided on at these levels.
+                self.core.push(l);
+            } else {
+                let reason = self.reason_lits(l.variable()).to_vec();
+                for q in reason.into_iter().skip(1) {
+                    if self.trail.level(q.variable()) > 0 {
+                        self.seen[q.variable().index() as usize] = true;
+                    }
+                }
+            }
+            self.seen[v] = false;
+        }
+
+        self.seen[p.variable().index() as usize] = false;
+    }
+
+    // Literal Block Distance: how many different decision levels a clause
+    // spans. Lower is better.
+    fn lbd(&self, lits: &[lit::Literal]) -> usize {
+        let mut levels: Vec<usize> = lits
+            .iter()
+            .map(|l| self.trail.level(l.variable()))
+            .collect();
+        levels.sort_unstable();
+        levels.dedup();
+        levels.len()
+    }
+
+    fn pick_branch(&mut self) -> Option<lit::Literal> {
+        if self.config.random_decisions > 0.0
+            && !self.order.heap.is_empty()
+            && self.rng.chance(self.config.random_decisions)
+        {
+            // Leave it in the heap, it will be skipped when popped if it's
+            // still assigned.
+            let v = self.order.heap[self.rng.below(self.order.heap.len())];
+            let var = lit::Variable::new(v);
+            if self.trail.get_var(var) == a::Assumption::Unknown {
+                return Some(lit::Literal::new(var, self.phases[v as usize]));
+            }
+        }
+
+        while let Some(v) = self.order.pop() {
+            let var = lit::Variable::new(v);
+            if self.trail.get_var(var) == a::Assumption::Unknown {
+                return Some(lit::Literal::new(var, self.phases[v as usize]));
+            }
+        }
+        None
+    }
+
+    // Throw away the less useful half of the learnt clauses.
+    fn reduce_learnts(&mut self) {
+        let mut learnts: Vec<usize> = (0..self.clauses.len())
+            .filter(|ci| {
+                let c = &self.clauses[*ci];
+                c.learnt && !c.deleted && c.lbd > 2
+            })
+            .collect();
+        learnts.sort_by_key(|ci| std::cmp::Reverse(self.clauses[*ci].lbd));
+
+        for ci in learnts.iter().take(learnts.len() / 2).copied() {
+            // Clauses which are the reason for an assignment must stay.
+            let first = self.clauses[ci].lits[0];
+            let locked = self.trail.is_true(first)
+                && matches!(self.reasons[first.variable().index() as usize], Some(Implied::Clause(r)) if r == ci);
+            if !locked {
+                self.clauses[ci].deleted = true;
+                self.clauses[ci].lits = vec![];
+            }
+        }
+    }
+}
+
+/// The Luby sequence: 1, 1, 2, 1, 1, 2, 4, 1, 1, 2, ...
+fn luby(mut i: u64) -> u64 {
+    let mut size = 1;
+    let mut seq = 0;
+    while size < i + 1 {
+        seq += 1;
+        size = 2 * size + 1;
+    }
+
+    while size - 1 != i {
+        size = (size - 1) >> 1;
+        seq -= 1;
+        i %= size;
+    }
+
+    1 << seq
+}
+
+// Variables ordered by activity (VSIDS), as a binary max-heap which knows where
+// each variable is so their activity can be bumped.
+#[derive(Debug)]
+struct VarOrder {
+    heap: Vec<u32>,
+    positions: Vec<Option<usize>>,
+    activity: Vec<f64>,
+    increment: f64,
+    decay: f64,
+}
+
+impl VarOrder {
+    fn new(decay: f64) -> VarOrder {
+        VarOrder {
+            heap: vec![],
+            positions: vec![],
+            activity: vec![],
+            increment: 1.0,
+            decay,
+        }
+    }
+
+    fn insert(&mut self, v: u32) {
+        let vi = v as usize;
+        if vi >= self.positions.len() {
+            self.positions.resize(vi + 1, None);
+            self.activity.resize(vi + 1, 0.0);
+        }
+        if self.positions[vi].is_some() {
+            return;
+        }
+
+        self.heap.push(v);
+        self.positions[vi] = Some(self.heap.len() - 1);
+        self.sift_up(self.heap.len() - 1);
+    }
+
+    fn pop(&mut self) -> Option<u32> {
+        if self.heap.is_empty() {
+            return None;
+        }
+
+        let top = self.heap.swap_remove(0);
+        self.positions[top as usize] = None;
+        if !self.heap.is_empty() {
+            self.positions[self.heap[0] as usize] = Some(0);
+            self.sift_down(0);
+        }
+        Some(top)
+    }
+
+    fn bump(&mut self, v: u32) {
+        let vi = v as usize;
+        self.activity[vi] += self.increment;
+
+        if self.activity[vi] > 1e100 {
+            for act in self.activity.iter_mut() {
+                *act *= 1e-100;
+            }
+            self.increment *= 1e-100;
+        }
+
+        if let Some(pos) = self.positions[vi] {
+            self.sift_up(pos);
+        }
+    }
+
+    fn decay(&mut self) {
+        self.increment /= self.decay;
+    }
+
+    fn higher(&self, i: usize, j: usize) -> bool {
+        self.activity[self.heap[i] as usize] > self.activity[self.heap[j] as usize]
+    }
+
+    fn swap(&mut self, i: usize, j: usize) {
+        self.heap.swap(i, j);
+        self.positions[self.heap[i] as usize] = Some(i);
+        self.positions[self.heap[j] as usize] = Some(j);
+    }
+
+    fn sift_up(&mut self, mut i: usize) {
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if !self.higher(i, parent) {
+                break;
+            }
+            self.swap(i, parent);
+            i = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut i: usize) {
+        loop {
+            let left = 2 * i + 1;
+            let right = left + 1;
+            let mut best = i;
+            if left < self.heap.len() && self.higher(left, best) {
+                best = left;
+            }
+            if right < self.heap.len() && self.higher(right, best) {
+                best = right;
+            }
+            if best == i {
+                break;
+            }
+            self.swap(i, best);
+            i = best;
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::clause as cls;
+
+    fn lit(l: i32) -> lit::Literal {
+        lit::Literal::from_dimacs(l).unwrap()
+    }
+
+    // The pigeonhole problem, `holes + 1` pigeons into `holes` holes, which
+    // is unsatisfiable, and hard for clause learning.
+    pub(crate) fn pigeons(holes: i32) -> Vec<cls::Clause> {
+        let var = |p: i32, h: i32| p * holes + h + 1;
+        let mut clauses = vec![];
+        for p in 0..=holes {
+            clauses.push((0..holes).map(|h| lit(var(p, h))).collect());
+        }
+        for h in 0..holes {
+            for p in 0..=holes {
+                for q in (p + 1)..=holes {
+                    clauses.push([-var(p, h), -var(q, h)].iter().copied().map(lit).collect());
+                }
+            }
+        }
+        clauses
+    }
+
+    fn pigeonhole_solver(holes: i32) -> Solver {
+        let mut solver = Solver::new();
+        for c in &pigeons(holes) {
+            solver.add_clause(c.iter().copied());
+        }
+        solver
+    }
+
+    fn solver(clauses: &[&[i32]]) -> Solver {
+        let mut solver = Solver::new();
+        for c in clauses {
+            solver.add_clause(c.iter().copied().map(lit));
+        }
+        solver
+    }
+
+    fn satisfies(model: &[lit::Literal], clauses: &[&[i32]]) -> bool {
+        clauses
+            .iter()
+            .all(|c| c.iter().any(|l| model.contains(&lit(*l))))
+    }
+
+    #[test]
+    fn luby_sequence() {
+        let seq: Vec<u64> = (0..10).map(luby).collect();
+        assert_eq!(seq, vec![1, 1, 2, 1, 1, 2, 4, 1, 1, 2]);
+    }
+
+    #[test]
+    fn empty() {
+        let mut s = solver(&[]);
+        assert_eq!(s.solve(), Answer::Satisfiable);
+        assert!(s.model().is_empty());
+    }
+
+    #[test]
+    fn empty_clause() {
+        let mut s = solver(&[&[]]);
+        assert_eq!(s.solve(), Answer::Unsatisfiable);
+    }
+
+    #[test]
+    fn simple_sat() {
+        let clauses: &[&[i32]] = &[&[1, 2], &[-1, 3], &[-3, -2], &[2, 3, -4]];
+        let mut s = solver(clauses);
+
+        assert_eq!(s.solve(), Answer::Satisfiable);
+        assert!(satisfies(s.model(), clauses));
+        assert_eq!(s.model().len(), 4);
+    }
+
+    #[test]
+    fn simple_unsat() {
+        let mut s = solver(&[&[1, 2], &[-1, 2], &[1, -2], &[-1, -2]]);
+        assert_eq!(s.solve(), Answer::Unsatisfiable);
+    }
+
+    #[test]
+    fn pigeonhole() {
+        let mut s = pigeonhole_solver(5);
+
+        assert_eq!(s.solve(), Answer::Unsatisfiable);
+        assert!(s.statistics().conflicts > 0);
+    }
+
+    #[test]
+    fn assumptions() {
+        let mut s = solver(&[&[-1, 2], &[-2, 3], &[-4, -3]]);
+
+        assert_eq!(s.solve_with(&[lit(1)]), Answer::Satisfiable);
+        assert!(s.model().contains(&lit(-4)));
+
+        assert_eq!(
+            s.solve_with(&[lit(5), lit(1), lit(4)]),
+            Answer::Unsatisfiable
+        );
+        let mut core = s.core().to_vec();
+        core.sort();
+        assert_eq!(core, vec![lit(1), lit(4)]);
+
+        // Assumptions don't stick around.
+        assert_eq!(s.solve(), Answer::Satisfiable);
+    }
+
+    #[test]
+    fn configurations() {
+        let clauses = pigeons(4);
+        let sat: &[&[i32]] = &[&[1, 2, 3], &[-1, -2], &[-2, -3], &[-1, -3], &[4, 5]];
+
+        for config in [
+            Config {
+                seed: 3,
+                restarts: Restarts::Geometric {
+                    first: 10,
+                    factor: 1.5,
+                },
+                var_decay: 0.8,
+                random_decisions: 0.2,
+                initial_phase: true,
+            },
+            Config {
+                seed: 4,
+                restarts: Restarts::Luby { unit: 1 },
+                ..Config::default()
+            },
+        ] {
+            let mut s = Solver::with_config(config.clone());
+            for c in &clauses {
+                s.add_clause(c.iter().copied());
+            }
+            assert_eq!(s.solve(), Answer::Unsatisfiable);
+
+            let mut s = Solver::with_config(config);
+            for c in sat {
+                s.add_clause(c.iter().copied().map(lit));
+            }
+            assert_eq!(s.solve(), Answer::Satisfiable);
+            assert!(satisfies(s.model(), sat));
+        }
+    }
+
+    #[test]
+    fn interrupt() {
+        let mut s = pigeonhole_solver(8);
+
+        let flag = Arc::new(AtomicBool::new(true));
+        s.set_interrupt(flag.clone());
+        assert_eq!(s.solve(), Answer::Unknown);
+        assert_eq!(s.statistics().conflicts, 0);
+
+        // It still works afterwards.
+        flag.store(false, Ordering::Relaxed);
+        s.add_clause(vec![lit(1)]);
+        assert_eq!(s.solve_with(&[lit(-1)]), Answer::Unsatisfiable);
+    }
+
+    type Sent = Arc<std::sync::Mutex<Vec<(Vec<lit::Literal>, usize)>>>;
+
+    // Remembers what it was given, and hands out a fixed set of clauses.
+    struct Mailbox {
+        sent: Sent,
+        inbox: Vec<Vec<lit::Literal>>,
+    }
+
+    impl Exchange for Mailbox {
+        fn export(&mut self, lits: &[lit::Literal], lbd: usize) {
+            self.sent.lock().unwrap().push((lits.to_vec(), lbd));
+        }
+
+        fn import(&mut self) -> Vec<Vec<lit::Literal>> {
+            std::mem::take(&mut self.inbox)
+        }
+    }
+
+    #[test]
+    fn exchange() {
+        let mut s = pigeonhole_solver(5);
+
+        let sent = Arc::new(std::sync::Mutex::new(vec![]));
+        s.set_exchange(Box::new(Mailbox {
+            sent: sent.clone(),
+            inbox: vec![],
+        }));
+        assert_eq!(s.solve(), Answer::Unsatisfiable);
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len() as u64, s.statistics().conflicts - 1);
+        assert!(sent
+            .iter()
+            .all(|(lits, lbd)| *lbd >= 1 && *lbd <= lits.len()));
+
+        // Clauses from elsewhere are learnt at restarts. These contradict
+        // each other, so we can tell they were used.
+        let mut s = Solver::with_config(Config {
+            restarts: Restarts::Luby { unit: 1 },
+            ..Config::default()
+        });
+        for c in &pigeons(5) {
+            s.add_clause(c.iter().copied());
+        }
+        s.set_exchange(Box::new(Mailbox {
+            sent: Arc::new(std::sync::Mutex::new(vec![])),
+            inbox: vec![vec![lit(-1), lit(2)], vec![lit(1)], vec![lit(-2)]],
+        }));
+        assert_eq!(s.solve(), Answer::Unsatisfiable);
+        assert_eq!(s.statistics().restarts, 1);
+        assert_eq!(s.statistics().imported, 3);
+    }
+
+    #[test]
+    fn incremental() {
+        let mut s = solver(&[&[1, 2]]);
+        assert_eq!(s.solve(), Answer::Satisfiable);
+
+        s.add_clause(vec![lit(-1)]);
+        assert_eq!(s.solve(), Answer::Satisfiable);
+        assert!(s.model().contains(&lit(2)));
+
+        s.add_clause(vec![lit(-2)]);
+        assert_eq!(s.solve(), Answer::Unsatisfiable);
+    }
+}
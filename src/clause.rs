@@ -7,9 +7,25 @@ use super::assumptions as ac;
 use super::literal as lit;
 
 /// A Clause is a *disjunction* of literals, i.e. `x OR y OR z`.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Clause(Vec<lit::Literal>);
 
+impl Default for Clause {
+    fn default() -> Clause {
+        Clause::new()
+    }
+}
+
+impl std::iter::FromIterator<lit::Literal> for Clause {
+    fn from_iter<I: IntoIterator<Item = lit::Literal>>(iter: I) -> Clause {
+        let mut clause = Clause::new();
+        for l in iter {
+            clause.add_literal(l);
+        }
+        clause
+    }
+}
+
 impl Clause {
     pub fn new() -> Clause {
         Clause(vec![])
@@ -19,6 +35,14 @@ impl Clause {
         self.0.iter()
     }
 
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     /// Searches for `var` in self, returning the index it appears at, and the
     /// literal containing the variable because different algorithms need both
     /// or either of course.
@@ -64,6 +88,7 @@ impl Clause {
 
     /// Under the given `assumptions` is this clause a unit clause? If it is, return the literal
     /// that is a Unit.
+    #[allow(clippy::needless_return)]
     pub fn get_unit(&self, assumptions: &ac::AssumptionStore) -> Option<lit::Literal> {
         // We're going to treat this as a one-element array for storing a possible unknown unit literal.
         let mut unit = None;
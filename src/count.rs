@@ -0,0 +1,632 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Exact model counting (#SAT).
+//!
+//! This is DPLL, but rather than stopping at the first solution, we add up the
+//! solutions on both sides of every decision. Two things make that bearable:
+//! - Component decomposition: once the unsatisfied clauses fall into groups
+//!   that share no unassigned variables, each group can be counted on its own,
+//!   and the counts multiplied together.
+//! - Component caching: the same group of clauses often turns up again under
+//!   different assignments, so we remember what each one counted to.
+//!
+//! The same search also counts models projected onto some of the variables
+//! (by branching on those first, and only looking for one way to satisfy
+//! whatever is left), and adds up the weights of models rather than counting
+//! them.
+
+use super::bigint::BigUint;
+use super::clause as cls;
+use super::literal as lit;
+
+use std::collections::HashMap;
+
+type Problem<'a> = &'a [cls::Clause];
+
+/// The number of assignments to variables `1..=variables` which satisfy every
+/// clause. Variables in no clause are free, and double the count. Any variable
+/// in a clause is counted, even if it is larger than `variables`.
+pub fn count(variables: u32, clauses: Problem) -> BigUint {
+    Counter::<BigUint>::new(variables, clauses, None, &[]).run()
+}
+
+/// The number of assignments to the `show` variables which can be extended
+/// to satisfy every clause. The other variables are only there to be solved
+/// for, so they never add to the count, even if they're free.
+pub fn count_projected(variables: u32, clauses: Problem, show: &[lit::Variable]) -> BigUint {
+    Counter::<BigUint>::new(variables, clauses, Some(show), &[]).run()
+}
+
+/// The total weight of the models, where a model weighs the product of the
+/// weights of its literals. Literals without a weight weigh 1.
+///
+/// With `show`, this is the total weight of the projected models, like
+/// `count_projected`, and only the literals of `show` variables have weights.
+pub fn weighted_count(
+    variables: u32,
+    clauses: Problem,
+    show: Option<&[lit::Variable]>,
+    weights: &[(lit::Literal, f64)],
+) -> f64 {
+    Counter::<f64>::new(variables, clauses, show, weights).run()
+}
+
+// What models add up to: how many there are, or how much they weigh.
+trait Weight: Clone {
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn is_zero(&self) -> bool;
+    fn plus(&self, other: &Self) -> Self;
+    fn times(&self, other: &Self) -> Self;
+}
+
+impl Weight for BigUint {
+    fn zero() -> BigUint {
+        BigUint::zero()
+    }
+
+    fn one() -> BigUint {
+        BigUint::one()
+    }
+
+    fn is_zero(&self) -> bool {
+        BigUint::is_zero(self)
+    }
+
+    fn plus(&self, other: &BigUint) -> BigUint {
+        self + other
+    }
+
+    fn times(&self, other: &BigUint) -> BigUint {
+        self * other
+    }
+}
+
+impl Weight for f64 {
+    fn zero() -> f64 {
+        0.0
+    }
+
+    fn one() -> f64 {
+        1.0
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == 0.0
+    }
+
+    fn plus(&self, other: &f64) -> f64 {
+        self + other
+    }
+
+    fn times(&self, other: &f64) -> f64 {
+        self * other
+    }
+}
+
+struct Counter<W> {
+    // Without tautologies, which can never be unsatisfied.
+    clauses: Vec<Vec<lit::Literal>>,
+    // For each literal (by `code`), the clauses it appears in.
+    occurrences: Vec<Vec<usize>>,
+    // For each variable, whether it's counted, or only solved for.
+    projected: Vec<bool>,
+    // For each literal (by `code`), its weight.
+    weights: Vec<W>,
+    values: Vec<Option<bool>>,
+    trail: Vec<lit::Literal>,
+    cache: HashMap<Vec<i32>, W>,
+}
+
+impl<W: Weight> Counter<W> {
+    fn new(
+        variables: u32,
+        clauses: Problem,
+        show: Option<&[lit::Variable]>,
+        weights: &[(lit::Literal, W)],
+    ) -> Counter<W> {
+        let vars = clauses
+            .iter()
+            .flat_map(|c| c.iter().map(|l| l.variable()))
+            .chain(show.unwrap_or(&[]).iter().copied())
+            .chain(weights.iter().map(|(l, _)| l.variable()))
+            .map(|v| v.index())
+            .max()
+            .unwrap_or(0)
+            .max(variables) as usize;
+
+        let clauses: Vec<Vec<lit::Literal>> = clauses
+            .iter()
+            .filter(|c| !c.iter().any(|l| c.iter().any(|m| *m == l.negate())))
+            .map(|c| c.iter().copied().collect())
+            .collect();
+
+        let mut occurrences = vec![vec![]; 2 * (vars + 1)];
+        for (idx, c) in clauses.iter().enumerate() {
+            for l in c {
+                occurrences[l.code()].push(idx);
+            }
+        }
+
+        let mut projected = vec![show.is_none(); vars + 1];
+        for v in show.unwrap_or(&[]) {
+            projected[v.index() as usize] = true;
+        }
+
+        let mut literal_weights = vec![W::one(); 2 * (vars + 1)];
+        for (l, w) in weights {
+            if projected[l.variable().index() as usize] {
+                literal_weights[l.code()] = w.clone();
+            }
+        }
+
+        Counter {
+            clauses,
+            occurrences,
+            projected,
+            weights: literal_weights,
+            values: vec![None; vars + 1],
+            trail: vec![],
+            cache: HashMap::new(),
+        }
+    }
+
+    fn run(&mut self) -> W {
+        let mut live = vec![];
+        for idx in 0..self.clauses.len() {
+            match self.clauses[idx].as_slice() {
+                [] => return W::zero(),
+                [l] => {
+                    if !self.assign(*l) {
+                        return W::zero();
+                    }
+                }
+                _ => live.push(idx),
+            }
+        }
+        if !self.propagate(0) {
+            return W::zero();
+        }
+
+        let vars: Vec<usize> = (1..self.values.len()).collect();
+        self.settle(0, &vars, &live)
+    }
+
+    fn value(&self, l: lit::Literal) -> Option<bool> {
+        self.values[l.variable().index() as usize].map(|v| v == l.polarity())
+    }
+
+    // Returns `false` if `l` is already false.
+    fn assign(&mut self, l: lit::Literal) -> bool {
+        match self.value(l) {
+            Some(v) => v,
+            None => {
+                self.values[l.variable().index() as usize] = Some(l.polarity());
+                self.trail.push(l);
+                true
+            }
+        }
+    }
+
+    // Unit propagate everything on the trail from `from` onwards. Returns
+    // `false` on a conflict.
+    fn propagate(&mut self, from: usize) -> bool {
+        let mut qhead = from;
+        while qhead < self.trail.len() {
+            let false_lit = self.trail[qhead].negate();
+            qhead += 1;
+
+            for i in 0..self.occurrences[false_lit.code()].len() {
+                let ci = self.occurrences[false_lit.code()][i];
+                let mut unassigned = None;
+                let mut count = 0;
+                let mut satisfied = false;
+                for l in &self.clauses[ci] {
+                    match self.value(*l) {
+                        Some(true) => {
+                            satisfied = true;
+                            break;
+                        }
+                        Some(false) => (),
+                        None => {
+                            unassigned = Some(*l);
+                            count += 1;
+                        }
+                    }
+                }
+
+                if satisfied {
+                    continue;
+                }
+                match (count, unassigned) {
+                    (0, _) => return false,
+                    (1, Some(l)) => {
+                        self.assign(l);
+                    }
+                    _ => (),
+                }
+            }
+        }
+        true
+    }
+
+    fn backtrack(&mut self, len: usize) {
+        for l in self.trail.drain(len..) {
+            self.values[l.variable().index() as usize] = None;
+        }
+    }
+
+    fn is_satisfied(&self, ci: usize) -> bool {
+        self.clauses[ci]
+            .iter()
+            .any(|l| self.value(*l) == Some(true))
+    }
+
+    // The unassigned variables in some clauses, without duplicates.
+    fn unassigned_vars(&self, clauses: &[usize]) -> Vec<usize> {
+        let mut vars: Vec<usize> = clauses
+            .iter()
+            .flat_map(|ci| self.clauses[*ci].iter())
+            .filter(|l| self.value(**l).is_none())
+            .map(|l| l.variable().index() as usize)
+            .collect();
+        vars.sort_unstable();
+        vars.dedup();
+        vars
+    }
+
+    // Split unsatisfied clauses into groups which share no unassigned
+    // variables.
+    fn components(&self, clauses: &[usize]) -> Vec<Vec<usize>> {
+        // Union-find over variables.
+        let mut parent: HashMap<usize, usize> = HashMap::new();
+        fn find(parent: &mut HashMap<usize, usize>, v: usize) -> usize {
+            let p = *parent.entry(v).or_insert(v);
+            if p == v {
+                return v;
+            }
+            let root = find(parent, p);
+            parent.insert(v, root);
+            root
+        }
+
+        for ci in clauses {
+            let mut vars = self.clauses[*ci]
+                .iter()
+                .filter(|l| self.value(**l).is_none())
+                .map(|l| l.variable().index() as usize);
+            if let Some(first) = vars.next() {
+                let root = find(&mut parent, first);
+                for v in vars {
+                    let other = find(&mut parent, v);
+                    parent.insert(other, root);
+                }
+            }
+        }
+
+        let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+        for ci in clauses {
+            let v = self.clauses[*ci]
+                .iter()
+                .find(|l| self.value(**l).is_none())
+                .map(|l| l.variable().index() as usize)
+                .expect("Unsatisfied clauses have unassigned literals.");
+            groups.entry(find(&mut parent, v)).or_default().push(*ci);
+        }
+
+        let mut groups: Vec<Vec<usize>> = groups.into_values().collect();
+        // Keep the order stable, so counting is deterministic.
+        groups.sort();
+        groups
+    }
+
+    // After propagating, the weight of what is left: the literals assigned
+    // since `len`, the variables of `vars` which no clause needs any more
+    // (either value will do), and the count of each component of what's left
+    // of `clauses`.
+    fn settle(&mut self, len: usize, vars: &[usize], clauses: &[usize]) -> W {
+        let mut total = W::one();
+        for l in &self.trail[len..] {
+            total = total.times(&self.weights[l.code()]);
+        }
+
+        let remaining: Vec<usize> = clauses
+            .iter()
+            .copied()
+            .filter(|ci| !self.is_satisfied(*ci))
+            .collect();
+        let constrained = self.unassigned_vars(&remaining);
+        for v in vars {
+            if self.values[*v].is_none()
+                && self.projected[*v]
+                && constrained.binary_search(v).is_err()
+            {
+                let var = lit::Variable::new(*v as u32);
+                let either = self.weights[lit::Literal::new(var, true).code()]
+                    .plus(&self.weights[lit::Literal::new(var, false).code()]);
+                total = total.times(&either);
+            }
+        }
+
+        for component in self.components(&remaining) {
+            let n = self.count(&component);
+            if n.is_zero() {
+                return n;
+            }
+            total = total.times(&n);
+        }
+        total
+    }
+
+    // Count the assignments to the unassigned variables of `clauses`, which
+    // are unsatisfied and form a single component.
+    //
+    // Without projected variables, all that matters is whether there is any
+    // model, so this counts to one at most.
+    fn count(&mut self, clauses: &[usize]) -> W {
+        let key = self.key(clauses);
+        if let Some(n) = self.cache.get(&key) {
+            return n.clone();
+        }
+
+        let vars = self.unassigned_vars(clauses);
+        let existential = !vars.iter().any(|v| self.projected[*v]);
+
+        // Branch on the variable in the most clauses, but projected variables
+        // first, because the others only need one value that works.
+        let mut occurrences = HashMap::new();
+        for ci in clauses {
+            for l in &self.clauses[*ci] {
+                if self.value(*l).is_none() {
+                    *occurrences.entry(l.variable()).or_insert(0) += 1;
+                }
+            }
+        }
+        let var = occurrences
+            .into_iter()
+            .max_by_key(|(v, n)| {
+                let projected = self.projected[v.index() as usize];
+                (projected, *n, std::cmp::Reverse(*v))
+            })
+            .map(|(v, _)| v)
+            .unwrap();
+
+        let mut total = W::zero();
+        for polarity in [true, false] {
+            let len = self.trail.len();
+            self.assign(lit::Literal::new(var, polarity));
+
+            if self.propagate(len) {
+                total = total.plus(&self.settle(len, &vars, clauses));
+            }
+
+            self.backtrack(len);
+            if existential && !total.is_zero() {
+                break;
+            }
+        }
+
+        self.cache.insert(key, total.clone());
+        total
+    }
+
+    // What's left of `clauses` under the current assignment, which is all
+    // that matters to their count.
+    fn key(&self, clauses: &[usize]) -> Vec<i32> {
+        let mut residual: Vec<Vec<i32>> = clauses
+            .iter()
+            .map(|ci| {
+                let mut c: Vec<i32> = self.clauses[*ci]
+                    .iter()
+                    .filter(|l| self.value(**l).is_none())
+                    .map(|l| dimacs(*l))
+                    .collect();
+                c.sort_unstable();
+                c
+            })
+            .collect();
+        residual.sort_unstable();
+
+        let mut key = vec![];
+        for c in residual {
+            key.extend(c);
+            key.push(0);
+        }
+        key
+    }
+}
+
+fn dimacs(l: lit::Literal) -> i32 {
+    let v = l.variable().index() as i32;
+    if l.polarity() {
+        v
+    } else {
+        -v
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Random;
+
+    fn lit(l: i32) -> lit::Literal {
+        lit::Literal::from_dimacs(l).unwrap()
+    }
+
+    macro_rules! c {
+        [] => ( cls::Clause::new() );
+        [$($e:expr),+ $(,)?] => ({
+            [$($e),+].iter().copied().map(lit).collect::<cls::Clause>()
+        })
+    }
+
+    fn satisfies(bits: u64, clauses: Problem) -> bool {
+        clauses.iter().all(|c| {
+            c.iter().any(|l| {
+                let value = bits >> (l.variable().index() - 1) & 1 == 1;
+                value == l.polarity()
+            })
+        })
+    }
+
+    // Try every assignment.
+    fn brute_force(variables: u32, clauses: Problem) -> u64 {
+        (0..1u64 << variables)
+            .filter(|bits| satisfies(*bits, clauses))
+            .count() as u64
+    }
+
+    fn random_problem(rng: &mut Random) -> (u32, Vec<cls::Clause>) {
+        let vars = 1 + rng.below(10) as u32;
+        let clauses = (0..rng.below(20))
+            .map(|_| {
+                (0..1 + rng.below(3))
+                    .map(|_| {
+                        let v = lit::Variable::new(1 + rng.below(vars as usize) as u32);
+                        lit::Literal::new(v, rng.chance(0.5))
+                    })
+                    .collect()
+            })
+            .collect();
+        (vars, clauses)
+    }
+
+    #[test]
+    fn trivial() {
+        assert_eq!(count(0, &[]).to_u64(), Some(1));
+        assert_eq!(count(3, &[]).to_u64(), Some(8));
+        assert_eq!(count(3, &[c![]]).to_u64(), Some(0));
+        assert_eq!(count(1, &[c![1], c![-1]]).to_u64(), Some(0));
+        assert_eq!(count(2, &[c![1, -1]]).to_u64(), Some(4));
+    }
+
+    #[test]
+    fn unused_variables() {
+        // 1 OR 2 has three solutions, and 3 to 10 are free.
+        assert_eq!(count(10, &[c![1, 2]]).to_u64(), Some(3 * 256));
+        // Variables in clauses count even if the header is too small.
+        assert_eq!(count(1, &[c![1, 2]]).to_u64(), Some(3));
+    }
+
+    #[test]
+    fn components() {
+        // 50 independent copies of 1 XOR 2, which has two solutions.
+        let clauses: Vec<cls::Clause> = (0..50)
+            .flat_map(|i| vec![c![2 * i + 1, 2 * i + 2], c![-(2 * i + 1), -(2 * i + 2)]])
+            .collect();
+        assert_eq!(count(100, &clauses), BigUint::power_of_two(50));
+        assert_eq!(count(150, &clauses), BigUint::power_of_two(100));
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = Random::new(11);
+        for _ in 0..200 {
+            let (vars, clauses) = random_problem(&mut rng);
+            assert_eq!(
+                count(vars, &clauses).to_u64(),
+                Some(brute_force(vars, &clauses))
+            );
+        }
+    }
+
+    #[test]
+    fn projected() {
+        // 1 OR 2, projected onto 1: both values of 1 extend to a model.
+        let show = [lit::Variable::new(1)];
+        assert_eq!(count_projected(2, &[c![1, 2]], &show).to_u64(), Some(2));
+        // Unprojected variables don't count even when they're free.
+        assert_eq!(count_projected(9, &[c![1, 2]], &show).to_u64(), Some(2));
+        // 1 AND (2 XOR 3), projected onto 1.
+        let clauses = [c![1], c![2, 3], c![-2, -3]];
+        assert_eq!(count_projected(3, &clauses, &show).to_u64(), Some(1));
+        assert_eq!(
+            count_projected(3, &[c![1], c![-1]], &show).to_u64(),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn projected_matches_brute_force() {
+        let mut rng = Random::new(12);
+        for _ in 0..200 {
+            let (vars, clauses) = random_problem(&mut rng);
+            let show: Vec<lit::Variable> = (1..=vars)
+                .filter(|_| rng.chance(0.5))
+                .map(lit::Variable::new)
+                .collect();
+            let mask = show.iter().fold(0, |m, v| m | 1 << (v.index() - 1));
+
+            let mut projections: Vec<u64> = (0..1u64 << vars)
+                .filter(|bits| satisfies(*bits, &clauses))
+                .map(|bits| bits & mask)
+                .collect();
+            projections.sort_unstable();
+            projections.dedup();
+
+            assert_eq!(
+                count_projected(vars, &clauses, &show).to_u64(),
+                Some(projections.len() as u64)
+            );
+        }
+    }
+
+    #[test]
+    fn weighted() {
+        // 1 OR 2 where 1 is true with weight 0.3 and 2 with 0.6.
+        let weights = [(lit(1), 0.3), (lit(-1), 0.7), (lit(2), 0.6), (lit(-2), 0.4)];
+        let total = weighted_count(2, &[c![1, 2]], None, &weights);
+        assert!((total - (1.0 - 0.7 * 0.4)).abs() < 1e-9);
+
+        // Variables without weights weigh 1 either way, so free ones double it.
+        let total = weighted_count(3, &[c![1, 2]], None, &weights);
+        assert!((total - 2.0 * (1.0 - 0.7 * 0.4)).abs() < 1e-9);
+
+        // Projected onto 1, which is true in some model either way.
+        let show = [lit::Variable::new(1)];
+        let total = weighted_count(2, &[c![1, 2]], Some(&show), &weights);
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn weighted_matches_brute_force() {
+        let mut rng = Random::new(13);
+        for _ in 0..200 {
+            let (vars, clauses) = random_problem(&mut rng);
+            let mut weights = vec![];
+            for v in 1..=vars {
+                for polarity in [true, false] {
+                    if rng.chance(0.8) {
+                        let l = lit::Literal::new(lit::Variable::new(v), polarity);
+                        weights.push((l, rng.fraction()));
+                    }
+                }
+            }
+            let weight = |l: lit::Literal| {
+                weights
+                    .iter()
+                    .find(|(m, _)| *m == l)
+                    .map_or(1.0, |(_, w)| *w)
+            };
+
+            let expected: f64 = (0..1u64 << vars)
+                .filter(|bits| satisfies(*bits, &clauses))
+                .map(|bits| {
+                    (1..=vars)
+                        .map(|v| {
+                            let value = bits >> (v - 1) & 1 == 1;
+                            weight(lit::Literal::new(lit::Variable::new(v), value))
+                        })
+                        .product::<f64>()
+                })
+                .sum();
+
+            let total = weighted_count(vars, &clauses, None, &weights);
+            assert!((total - expected).abs() < 1e-9, "{} != {}", total, expected);
+        }
+    }
+}
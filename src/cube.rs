@@ -0,0 +1,445 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Cube-and-conquer, from "Cube and Conquer: Guiding CDCL SAT Solvers by
+//! Lookaheads" (Heule, Kullmann, Wieringa, Biere, 2011).
+//!
+//! A lookahead solver splits the problem into cubes: partial assignments which
+//! between them cover every solution. Each cube is then much easier than the
+//! whole problem, and they can be solved independently, in parallel, by an
+//! incremental CDCL solver which takes each cube as its assumptions.
+
+use super::cdcl;
+use super::clause as cls;
+use super::literal as lit;
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+type Problem<'a> = &'a [cls::Clause];
+
+/// A partial assignment, as a conjunction of literals.
+pub type Cube = Vec<lit::Literal>;
+
+/// How to split a problem into cubes.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Stop splitting after this many decisions, so there are at most
+    /// `2 ^ depth` cubes.
+    pub depth: usize,
+    /// How many variables to look ahead on at each split. The variables in
+    /// the most clauses are tried, as looking ahead on everything is slow.
+    pub candidates: usize,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            depth: 10,
+            candidates: 100,
+        }
+    }
+}
+
+/// Split `clauses` into cubes, using lookahead.
+///
+/// At each step, we try assigning each candidate variable both ways, and unit
+/// propagate. How much each side shrinks the unsatisfied clauses is its
+/// reduction, and we split on the variable where both sides reduce the
+/// problem most. If one side propagates to a conflict, the variable must have
+/// the other value (it is a failed literal), and if both do, the current
+/// cube can be thrown away.
+///
+/// The problem is unsatisfiable if and only if every cube is. In particular,
+/// if there are no cubes at all, the lookahead showed it is unsatisfiable.
+pub fn split(clauses: Problem, config: &Config) -> Vec<Cube> {
+    let mut lookahead = Lookahead::new(clauses);
+    let mut cubes = vec![];
+
+    for c in clauses {
+        if c.is_empty() {
+            return cubes;
+        }
+        if c.len() == 1 {
+            let l = c.iter().next().copied().unwrap();
+            if !lookahead.assign(l) {
+                return cubes;
+            }
+        }
+    }
+
+    if lookahead.propagate() {
+        lookahead.split(config.depth, config, &mut vec![], &mut cubes);
+    }
+    cubes
+}
+
+/// Solve each cube, with `threads` incremental solvers, each given the
+/// problem by `setup`. We stop as soon as one cube is satisfiable.
+///
+/// Returns the answer, and a model if it is `Satisfiable`.
+pub fn conquer<F>(cubes: &[Cube], threads: usize, setup: F) -> (cdcl::Answer, Vec<lit::Literal>)
+where
+    F: Fn(&mut cdcl::Solver) + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let stop = Arc::new(AtomicBool::new(false));
+    let model = Mutex::new(None);
+
+    thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            let (next, stop, model, setup) = (&next, &stop, &model, &setup);
+            scope.spawn(move || {
+                let mut solver = cdcl::Solver::new();
+                solver.set_interrupt(stop.clone());
+                setup(&mut solver);
+
+                loop {
+                    let idx = next.fetch_add(1, Ordering::Relaxed);
+                    if idx >= cubes.len() {
+                        return;
+                    }
+
+                    if solver.solve_with(&cubes[idx]) == cdcl::Answer::Satisfiable {
+                        let mut model = model.lock().unwrap();
+                        if model.is_none() {
+                            *model = Some(solver.model().to_vec());
+                        }
+                        stop.store(true, Ordering::Relaxed);
+                        return;
+                    }
+                    if stop.load(Ordering::Relaxed) {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+
+    match model.into_inner().unwrap() {
+        Some(model) => (cdcl::Answer::Satisfiable, model),
+        None => (cdcl::Answer::Unsatisfiable, vec![]),
+    }
+}
+
+// A partial assignment that can propagate and undo, which is all lookahead
+// needs.
+struct Lookahead {
+    clauses: Vec<Vec<lit::Literal>>,
+    // For each literal (by `code`), the clauses it appears in.
+    occurrences: Vec<Vec<usize>>,
+    values: Vec<Option<bool>>,
+    trail: Vec<lit::Literal>,
+    qhead: usize,
+    // Scratch space, to count each clause once.
+    stamps: Vec<usize>,
+    stamp: usize,
+}
+
+impl Lookahead {
+    fn new(clauses: Problem) -> Lookahead {
+        let vars = clauses
+            .iter()
+            .flat_map(|c| c.iter())
+            .map(|l| l.variable().index() as usize)
+            .max()
+            .unwrap_or(0);
+
+        let mut occurrences = vec![vec![]; 2 * (vars + 1)];
+        for (idx, c) in clauses.iter().enumerate() {
+            for l in c.iter() {
+                occurrences[l.code()].push(idx);
+            }
+        }
+
+        Lookahead {
+            clauses: clauses
+                .iter()
+                .map(|c| c.iter().copied().collect())
+                .collect(),
+            occurrences,
+            values: vec![None; vars + 1],
+            trail: vec![],
+            qhead: 0,
+            stamps: vec![0; clauses.len()],
+            stamp: 0,
+        }
+    }
+
+    fn value(&self, l: lit::Literal) -> Option<bool> {
+        self.values[l.variable().index() as usize].map(|v| v == l.polarity())
+    }
+
+    // Returns `false` if `l` is already false.
+    fn assign(&mut self, l: lit::Literal) -> bool {
+        match self.value(l) {
+            Some(v) => v,
+            None => {
+                self.values[l.variable().index() as usize] = Some(l.polarity());
+                self.trail.push(l);
+                true
+            }
+        }
+    }
+
+    // Unit propagation. Returns `false` on a conflict.
+    fn propagate(&mut self) -> bool {
+        while self.qhead < self.trail.len() {
+            let false_lit = self.trail[self.qhead].negate();
+            self.qhead += 1;
+
+            for ci in self.occurrences[false_lit.code()].clone() {
+                let mut unassigned = None;
+                let mut count = 0;
+                let mut satisfied = false;
+                for l in &self.clauses[ci] {
+                    match self.value(*l) {
+                        Some(true) => {
+                            satisfied = true;
+                            break;
+                        }
+                        Some(false) => (),
+                        None => {
+                            unassigned = Some(*l);
+                            count += 1;
+                        }
+                    }
+                }
+
+                if satisfied {
+                    continue;
+                }
+                match (count, unassigned) {
+                    (0, _) => return false,
+                    (1, Some(l)) => {
+                        self.assign(l);
+                    }
+                    _ => (),
+                }
+            }
+        }
+        true
+    }
+
+    fn backtrack(&mut self, len: usize) {
+        for l in self.trail.drain(len..) {
+            self.values[l.variable().index() as usize] = None;
+        }
+        self.qhead = self.qhead.min(len);
+    }
+
+    // Assign `l` and propagate, then measure how much that shrank the
+    // unsatisfied clauses, and undo it. Returns `None` on a conflict.
+    fn look(&mut self, l: lit::Literal) -> Option<f64> {
+        let len = self.trail.len();
+        self.assign(l);
+        let ok = self.propagate();
+
+        let mut reduction = 0.0;
+        if ok {
+            self.stamp += 1;
+            for i in len..self.trail.len() {
+                let false_lit = self.trail[i].negate();
+                for ci in self.occurrences[false_lit.code()].iter().copied() {
+                    if self.stamps[ci] == self.stamp {
+                        continue;
+                    }
+                    self.stamps[ci] = self.stamp;
+
+                    let c = &self.clauses[ci];
+                    if c.iter().any(|l| self.value(*l) == Some(true)) {
+                        continue;
+                    }
+                    // Shorter clauses are closer to propagating, so count
+                    // for more.
+                    let left = c.iter().filter(|l| self.value(**l).is_none()).count();
+                    reduction += 0.2f64.powi(left as i32 - 2);
+                }
+            }
+        }
+
+        self.backtrack(len);
+        ok.then_some(reduction)
+    }
+
+    // The unassigned variables in the most unsatisfied clauses.
+    fn candidates(&self, n: usize) -> Vec<lit::Variable> {
+        let mut counts = vec![0; self.values.len()];
+        for c in &self.clauses {
+            if c.iter().any(|l| self.value(*l) == Some(true)) {
+                continue;
+            }
+            for l in c {
+                if self.value(*l).is_none() {
+                    counts[l.variable().index() as usize] += 1;
+                }
+            }
+        }
+
+        let mut vars: Vec<usize> = (1..counts.len()).filter(|v| counts[*v] > 0).collect();
+        vars.sort_by_key(|v| std::cmp::Reverse(counts[*v]));
+        vars.truncate(n);
+        vars.into_iter()
+            .map(|v| lit::Variable::new(v as u32))
+            .collect()
+    }
+
+    fn split(&mut self, depth: usize, config: &Config, cube: &mut Cube, cubes: &mut Vec<Cube>) {
+        let len = self.trail.len();
+
+        let best = loop {
+            let mut best = None;
+            let mut best_score = -1.0;
+            let mut failed = false;
+
+            for var in self.candidates(config.candidates) {
+                if self.values[var.index() as usize].is_some() {
+                    // Assigned by a failed literal earlier in this loop.
+                    continue;
+                }
+
+                let pos = lit::Literal::new(var, true);
+                let forced = match (self.look(pos), self.look(pos.negate())) {
+                    (None, None) => {
+                        self.backtrack(len);
+                        return;
+                    }
+                    // A failed literal, so it must have the other value.
+                    (None, Some(_)) => pos.negate(),
+                    (Some(_), None) => pos,
+                    (Some(p), Some(n)) => {
+                        // Prefer variables where both sides reduce a lot.
+                        let score = p * n + p + n;
+                        if score > best_score {
+                            best_score = score;
+                            best = Some(var);
+                        }
+                        continue;
+                    }
+                };
+
+                self.assign(forced);
+                if !self.propagate() {
+                    self.backtrack(len);
+                    return;
+                }
+                failed = true;
+            }
+
+            // Failed literals change the reductions, so look again.
+            if !failed {
+                break best;
+            }
+        };
+
+        match best {
+            Some(var) if depth > 0 => {
+                for polarity in [true, false] {
+                    let l = lit::Literal::new(var, polarity);
+                    let before = self.trail.len();
+                    self.assign(l);
+                    cube.push(l);
+                    if self.propagate() {
+                        self.split(depth - 1, config, cube, cubes);
+                    }
+                    cube.pop();
+                    self.backtrack(before);
+                }
+            }
+            // Out of depth, or every clause is satisfied.
+            _ => cubes.push(cube.clone()),
+        }
+
+        self.backtrack(len);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdcl::tests::pigeons;
+
+    fn lit(l: i32) -> lit::Literal {
+        lit::Literal::from_dimacs(l).unwrap()
+    }
+
+    macro_rules! c {
+        [] => ( cls::Clause::new() );
+        [$($e:expr),+ $(,)?] => ({
+            [$($e),+].iter().copied().map(lit).collect::<cls::Clause>()
+        })
+    }
+
+    fn setup(clauses: Problem<'_>) -> impl Fn(&mut cdcl::Solver) + Sync + '_ {
+        move |solver| {
+            for c in clauses {
+                solver.add_clause(c.iter().copied());
+            }
+        }
+    }
+
+    #[test]
+    fn cubes_are_disjoint() {
+        let clauses = pigeons(5);
+        let config = Config {
+            depth: 4,
+            ..Config::default()
+        };
+        let cubes = split(&clauses, &config);
+
+        assert!(!cubes.is_empty());
+        assert!(cubes.len() <= 16);
+        for (i, a) in cubes.iter().enumerate() {
+            assert!(a.len() <= 4);
+            for b in &cubes[i + 1..] {
+                assert!(a.iter().any(|l| b.contains(&l.negate())));
+            }
+        }
+    }
+
+    #[test]
+    fn refuted_by_lookahead() {
+        // Every assignment to 1 and 2 fails.
+        let clauses = vec![c![1, 2, 3], c![1, 2, -3], c![-1, 2], c![1, -2], c![-1, -2]];
+        assert!(split(&clauses, &Config::default()).is_empty());
+
+        assert!(split(&[c![1], c![-1]], &Config::default()).is_empty());
+        assert!(split(&[c![]], &Config::default()).is_empty());
+    }
+
+    #[test]
+    fn satisfied_early() {
+        let cubes = split(&[c![1, 2]], &Config::default());
+        assert!(!cubes.is_empty());
+        assert!(cubes.iter().all(|c| c.len() <= 1));
+    }
+
+    #[test]
+    fn conquer_unsat() {
+        let clauses = pigeons(6);
+        let config = Config {
+            depth: 5,
+            ..Config::default()
+        };
+        let cubes = split(&clauses, &config);
+        let (answer, model) = conquer(&cubes, 3, setup(&clauses));
+
+        assert_eq!(answer, cdcl::Answer::Unsatisfiable);
+        assert!(model.is_empty());
+    }
+
+    #[test]
+    fn conquer_sat() {
+        // The last pigeon doesn't have to go anywhere, so the rest fit.
+        let mut clauses = pigeons(6);
+        clauses.remove(6);
+        let cubes = split(&clauses, &Config::default());
+        let (answer, model) = conquer(&cubes, 3, setup(&clauses));
+
+        assert_eq!(answer, cdcl::Answer::Satisfiable);
+        assert!(clauses.iter().all(|c| c.iter().any(|l| model.contains(l))));
+    }
+}
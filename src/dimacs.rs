@@ -3,12 +3,51 @@
 // See COPYRIGHT for details.
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+use super::bigint::BigUint;
 use super::clause as c;
 use super::literal as l;
 
 use std::io::{BufRead, Result, Write};
 use std::mem::swap;
 
+/// A DIMACS-CNF problem, as it was written.
+#[derive(Debug, Clone)]
+pub struct Cnf {
+    /// The number of variables the `p cnf` line declared. Some of them might
+    /// not be in any clause.
+    pub variables: u32,
+    pub clauses: Vec<c::Clause>,
+    /// The variables from `c p show 1 2 0` lines, to project model counts
+    /// onto, if there were any.
+    pub show: Option<Vec<l::Variable>>,
+    /// Literal weights from `c p weight -1 0.3 0` lines, for weighted model
+    /// counting.
+    pub weights: Vec<(l::Literal, f64)>,
+}
+
+/// A weighted DIMACS problem, for MaxSAT.
+#[derive(Debug, Clone)]
+pub struct Wcnf {
+    /// The number of variables the header declared, or without one, the
+    /// highest variable in any clause.
+    pub variables: u32,
+    /// Clauses which have to be satisfied.
+    pub hard: Vec<c::Clause>,
+    /// Clauses which should be satisfied, and what it costs if they aren't.
+    pub soft: Vec<(u64, c::Clause)>,
+}
+
+/// A group-oriented DIMACS problem, for group MUSes.
+#[derive(Debug, Clone)]
+pub struct Gcnf {
+    /// The number of variables the header declared.
+    pub variables: u32,
+    /// The clauses in group 0, which are always there.
+    pub hard: Vec<c::Clause>,
+    /// The clauses in each other group, so `groups[0]` is group 1.
+    pub groups: Vec<Vec<c::Clause>>,
+}
+
 /// A DIMACS-CNF File Parser
 ///
 /// The format is supposed to be stupid simple:
@@ -17,8 +56,39 @@ use std::mem::swap;
 /// - `34 -2 83 0` means a clause. Negated ints are negated literals. 0 means end
 ///   of clause.
 pub fn parse<R: BufRead>(buf: R) -> Option<Vec<c::Clause>> {
+    parse_problem(buf, Detail::Clauses).map(|cnf| cnf.clauses)
+}
+
+/// Parse DIMACS-CNF like `parse`, but keep the number of variables declared
+/// in the header, for when variables that aren't in any clause matter.
+pub fn parse_cnf<R: BufRead>(buf: R) -> Option<Cnf> {
+    parse_problem(buf, Detail::Variables)
+}
+
+/// Parse DIMACS-CNF like `parse_cnf`, but also understand the model counting
+/// competition's comments, which give a projection (`c p show 1 2 0`) and
+/// literal weights (`c p weight -1 0.3 0`). Malformed ones are an error.
+pub fn parse_annotated_cnf<R: BufRead>(buf: R) -> Option<Cnf> {
+    parse_problem(buf, Detail::Annotations)
+}
+
+// How much of a problem to read, beyond its clauses. Each includes the ones
+// before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Detail {
+    Clauses,
+    // The number of variables in the header, which has to be a number.
+    Variables,
+    // The model counting comments. Without them, comments are just comments,
+    // so a problem written for a different tool is still fine.
+    Annotations,
+}
+
+fn parse_problem<R: BufRead>(buf: R, detail: Detail) -> Option<Cnf> {
     let mut found_problem = false;
-    // let mut variables_len = 0;
+    let mut variables_len = 0;
+    let mut show: Option<Vec<l::Variable>> = None;
+    let mut weights = vec![];
     let mut clauses_left = 0;
     let mut current_clause = c::Clause::new();
     let mut all_clauses = vec![];
@@ -27,7 +97,32 @@ pub fn parse<R: BufRead>(buf: R) -> Option<Vec<c::Clause>> {
         if let Ok(mut line) = res {
             line.make_ascii_lowercase();
 
-            if line.starts_with("c") {
+            if let Some(vars) = line
+                .strip_prefix("c p show ")
+                .filter(|_| detail == Detail::Annotations)
+            {
+                // There can be several show lines, which add up.
+                let lits = parse_literals(vars)?;
+                if lits.iter().any(|lit| !lit.polarity()) {
+                    return None;
+                }
+                show.get_or_insert_with(Vec::new)
+                    .extend(lits.iter().map(|lit| lit.variable()));
+            } else if let Some(weight) = line
+                .strip_prefix("c p weight ")
+                .filter(|_| detail == Detail::Annotations)
+            {
+                let parts: Vec<_> = weight.split_ascii_whitespace().collect();
+                if parts.len() != 3 || parts[2] != "0" {
+                    return None;
+                }
+                let lit = l::Literal::from_dimacs(parts[0].parse().ok()?)?;
+                let weight: f64 = parts[1].parse().ok()?;
+                if !weight.is_finite() || weight < 0.0 {
+                    return None;
+                }
+                weights.push((lit, weight));
+            } else if line.starts_with("c") {
                 // Comment line
                 continue;
             } else if line.starts_with("p") {
@@ -44,12 +139,11 @@ pub fn parse<R: BufRead>(buf: R) -> Option<Vec<c::Clause>> {
                     return None;
                 }
 
-                // We don't really care about number of variables.
-                // if let Ok(len) = parts[2].parse() {
-                //     variables_len = len
-                // } else {
-                //     return None;
-                // }
+                if let Ok(len) = parts[2].parse() {
+                    variables_len = len
+                } else if detail >= Detail::Variables {
+                    return None;
+                }
 
                 if let Ok(len) = parts[3].parse() {
                     clauses_left = len
@@ -106,7 +200,186 @@ pub fn parse<R: BufRead>(buf: R) -> Option<Vec<c::Clause>> {
         return None;
     }
 
-    return Some(all_clauses);
+    Some(Cnf {
+        variables: variables_len,
+        clauses: all_clauses,
+        show,
+        weights,
+    })
+}
+
+/// A WCNF File Parser, for MaxSAT problems.
+///
+/// There are two formats, and we take either:
+/// - The old one is like DIMACS-CNF, but the header is
+///   `p wcnf <vars> <clauses> <top>`, and every clause starts with its weight,
+///   like `5 34 -2 0`. Clauses which weigh `top` or more are hard. Without a
+///   `top`, every clause is soft.
+/// - The new one (from the 2022 MaxSAT Evaluation) has no header. Hard clauses
+///   start with `h`, like `h 34 -2 0`, and soft ones with their weight. There
+///   are as many variables as the clauses mention.
+pub fn parse_wcnf<R: BufRead>(buf: R) -> Option<Wcnf> {
+    let mut header: Option<(u32, usize, u64)> = None;
+    // Whether we've seen a clause yet, after which it's too late for a header.
+    let mut started = false;
+    // The weight of the clause we're in the middle of, or `Some(None)` if it's
+    // hard.
+    let mut weight: Option<Option<u64>> = None;
+    let mut current_clause = c::Clause::new();
+    let mut hard = vec![];
+    let mut soft = vec![];
+    let mut variables = 0;
+
+    for res in buf.lines() {
+        let line = res.ok()?;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('c') {
+            continue;
+        } else if line.starts_with('p') {
+            if header.is_some() || started {
+                return None;
+            }
+
+            let parts: Vec<_> = line.split_ascii_whitespace().collect();
+            if parts.len() < 4 || parts.len() > 5 || parts[0] != "p" || parts[1] != "wcnf" {
+                return None;
+            }
+            let top = match parts.get(4) {
+                Some(top) => top.parse().ok()?,
+                None => u64::MAX,
+            };
+            header = Some((parts[2].parse().ok()?, parts[3].parse().ok()?, top));
+        } else {
+            started = true;
+            for token in line.split_ascii_whitespace() {
+                let Some(w) = weight else {
+                    weight = Some(match (token, header) {
+                        // Only the new format marks hard clauses.
+                        ("h", None) => None,
+                        (_, Some((_, _, top))) => {
+                            let w = token.parse().ok()?;
+                            if w >= top {
+                                None
+                            } else {
+                                Some(w)
+                            }
+                        }
+                        _ => Some(token.parse().ok()?),
+                    });
+                    continue;
+                };
+
+                match l::Literal::from_dimacs(token.parse().ok()?) {
+                    Some(lit) => {
+                        variables = variables.max(lit.variable().index());
+                        current_clause.add_literal(lit)
+                    }
+                    None => {
+                        let clause = std::mem::take(&mut current_clause);
+                        match w {
+                            None => hard.push(clause),
+                            Some(w) => soft.push((w, clause)),
+                        }
+                        weight = None;
+                    }
+                }
+            }
+        }
+    }
+
+    // Every clause has to be finished, and with a header, there should be as
+    // many as it said.
+    if weight.is_some() {
+        return None;
+    }
+    if let Some((declared, clauses, _)) = header {
+        if hard.len() + soft.len() != clauses {
+            return None;
+        }
+        variables = declared;
+    }
+
+    Some(Wcnf {
+        variables,
+        hard,
+        soft,
+    })
+}
+
+/// A GCNF File Parser, for problems whose clauses come in groups.
+///
+/// This is like DIMACS-CNF, but the header is `p gcnf <vars> <clauses>
+/// <groups>`, and every clause starts with its group, like `{2} 34 -2 0`.
+/// Group 0 is hard, and the others are numbered from 1 up to `groups`.
+pub fn parse_gcnf<R: BufRead>(buf: R) -> Option<Gcnf> {
+    let mut header: Option<(u32, usize)> = None;
+    let mut group: Option<usize> = None;
+    let mut current_clause = c::Clause::new();
+    let mut hard = vec![];
+    let mut groups: Vec<Vec<c::Clause>> = vec![];
+    let mut clauses = 0;
+
+    for res in buf.lines() {
+        let line = res.ok()?;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('c') {
+            continue;
+        } else if line.starts_with('p') {
+            if header.is_some() {
+                return None;
+            }
+
+            let parts: Vec<_> = line.split_ascii_whitespace().collect();
+            if parts.len() != 5 || parts[0] != "p" || parts[1] != "gcnf" {
+                return None;
+            }
+            let variables = parts[2].parse().ok()?;
+            clauses = parts[3].parse().ok()?;
+            header = Some((variables, clauses));
+            groups.resize_with(parts[4].parse().ok()?, Vec::new);
+        } else {
+            header?;
+            for token in line.split_ascii_whitespace() {
+                let Some(g) = group else {
+                    let g = token.strip_prefix('{')?.strip_suffix('}')?;
+                    let g: usize = g.parse().ok()?;
+                    if g > groups.len() {
+                        return None;
+                    }
+                    group = Some(g);
+                    continue;
+                };
+
+                match l::Literal::from_dimacs(token.parse().ok()?) {
+                    Some(lit) => current_clause.add_literal(lit),
+                    None => {
+                        let clause = std::mem::take(&mut current_clause);
+                        match g {
+                            0 => hard.push(clause),
+                            g => groups[g - 1].push(clause),
+                        }
+                        group = None;
+                        clauses = clauses.checked_sub(1)?;
+                    }
+                }
+            }
+        }
+    }
+
+    // Every clause has to be finished, and there should be as many as the
+    // header said.
+    let (variables, _) = header?;
+    if group.is_some() || clauses != 0 {
+        return None;
+    }
+
+    Some(Gcnf {
+        variables,
+        hard,
+        groups,
+    })
 }
 
 /// A DIMACS Solution Printer
@@ -120,11 +393,421 @@ pub fn print<W: Write>(buf: &mut W, soln: Option<Vec<l::Literal>>) -> Result<()>
         None => writeln!(buf, "s UNSATISFIABLE"),
         Some(soln) => {
             writeln!(buf, "s SATISFIABLE")?;
-            write!(buf, "v ")?;
-            for l in soln {
-                write!(buf, "{} ", l.to_dimacs())?;
+            print_values(buf, &soln)
+        }
+    }
+}
+
+/// Print just the values line of a solution, `v 34 -2 83 0`, for when there
+/// is more than one.
+pub fn print_values<W: Write>(buf: &mut W, soln: &[l::Literal]) -> Result<()> {
+    write!(buf, "v ")?;
+    for l in soln {
+        write!(buf, "{} ", l.to_dimacs())?;
+    }
+    writeln!(buf, "0")
+}
+
+/// Print that we don't know whether there is a solution, which incomplete
+/// solvers have to do when they give up.
+pub fn print_unknown<W: Write>(buf: &mut W) -> Result<()> {
+    writeln!(buf, "s UNKNOWN")
+}
+
+/// A Model Count Printer, in the format of the model counting competition.
+///
+/// `s SATISFIABLE` (or `UNSATISFIABLE` if there are none) is followed by the
+/// type of count, `c s type mc` (or `pmc` if it was `projected`), and the
+/// exact count, `c s exact arb int 42`.
+pub fn print_count<W: Write>(buf: &mut W, count: &BigUint, projected: bool) -> Result<()> {
+    if count.is_zero() {
+        writeln!(buf, "s UNSATISFIABLE")?;
+    } else {
+        writeln!(buf, "s SATISFIABLE")?;
+    }
+    writeln!(buf, "c s type {}", if projected { "pmc" } else { "mc" })?;
+    writeln!(buf, "c s exact arb int {}", count)
+}
+
+/// A Weighted Model Count Printer, like `print_count`, but for a type of `wmc`
+/// (or `pwmc`), with the log10 of the weight as an estimate, and the weight
+/// itself as `c s exact double prec-sci 1.5e-3`.
+///
+/// Models can weigh nothing, so we can't tell `UNSATISFIABLE` from a total
+/// weight of zero.
+pub fn print_weighted_count<W: Write>(buf: &mut W, weight: f64, projected: bool) -> Result<()> {
+    writeln!(buf, "s SATISFIABLE")?;
+    writeln!(buf, "c s type {}", if projected { "pwmc" } else { "wmc" })?;
+    writeln!(buf, "c s log10-estimate {}", weight.log10())?;
+    writeln!(buf, "c s exact double prec-sci {:e}", weight)
+}
+
+/// A DIMACS-CNF Printer for some of a problem's clauses, like a minimal
+/// unsatisfiable subset.
+///
+/// A `c clauses 1 4 7` comment says which of the problem's clauses they are,
+/// numbered from 1 in the order they were read, before the usual header and
+/// clauses.
+pub fn print_subset<W: Write>(
+    buf: &mut W,
+    variables: u32,
+    clauses: &[c::Clause],
+    indexes: &[usize],
+) -> Result<()> {
+    write!(buf, "c clauses")?;
+    for idx in indexes {
+        write!(buf, " {}", idx + 1)?;
+    }
+    writeln!(buf)?;
+    writeln!(buf, "p cnf {} {}", variables, indexes.len())?;
+    for idx in indexes {
+        for l in clauses[*idx].iter() {
+            write!(buf, "{} ", l.to_dimacs())?;
+        }
+        writeln!(buf, "0")?;
+    }
+    Ok(())
+}
+
+/// A GCNF Printer for some of a problem's groups, like a group MUS, along
+/// with its hard clauses in group 0.
+///
+/// Groups keep the numbers they had in the problem, which a `c groups 1 4 7`
+/// comment lists too.
+pub fn print_groups<W: Write>(buf: &mut W, gcnf: &Gcnf, indexes: &[usize]) -> Result<()> {
+    write!(buf, "c groups")?;
+    for idx in indexes {
+        write!(buf, " {}", idx + 1)?;
+    }
+    writeln!(buf)?;
+
+    let clauses = gcnf.hard.len() + indexes.iter().map(|i| gcnf.groups[*i].len()).sum::<usize>();
+    let groups = indexes.iter().max().map_or(0, |i| i + 1);
+    writeln!(buf, "p gcnf {} {} {}", gcnf.variables, clauses, groups)?;
+    let hard = gcnf.hard.iter().map(|c| (0, c));
+    let soft = indexes
+        .iter()
+        .flat_map(|i| gcnf.groups[*i].iter().map(move |c| (i + 1, c)));
+    for (g, clause) in hard.chain(soft) {
+        write!(buf, "{{{}}} ", g)?;
+        for l in clause.iter() {
+            write!(buf, "{} ", l.to_dimacs())?;
+        }
+        writeln!(buf, "0")?;
+    }
+    Ok(())
+}
+
+/// An iCNF Printer, for incremental problems.
+///
+/// This is DIMACS with a `p inccnf` header and no counts, followed by cubes:
+/// `a 34 -2 0` means solve the clauses again, assuming 34 and -2.
+pub fn print_icnf<W: Write>(
+    buf: &mut W,
+    clauses: &[c::Clause],
+    cubes: &[Vec<l::Literal>],
+) -> Result<()> {
+    writeln!(buf, "p inccnf")?;
+    for clause in clauses {
+        for l in clause.iter() {
+            write!(buf, "{} ", l.to_dimacs())?;
+        }
+        writeln!(buf, "0")?;
+    }
+    for cube in cubes {
+        print_cube(buf, cube)?;
+    }
+    Ok(())
+}
+
+/// Print a single iCNF cube, as `a 34 -2 0`.
+pub fn print_cube<W: Write>(buf: &mut W, cube: &[l::Literal]) -> Result<()> {
+    write!(buf, "a ")?;
+    for l in cube {
+        write!(buf, "{} ", l.to_dimacs())?;
+    }
+    writeln!(buf, "0")
+}
+
+/// Parse a line of literals terminated by 0, like `34 -2 83 0`, as used for
+/// clauses, cubes and values.
+pub fn parse_literals(line: &str) -> Option<Vec<l::Literal>> {
+    let mut lits = vec![];
+    for token in line.split_ascii_whitespace() {
+        match l::Literal::from_dimacs(token.parse().ok()?) {
+            Some(lit) => lits.push(lit),
+            None => return Some(lits),
+        }
+    }
+    // Never terminated.
+    None
+}
+
+/// Print an intermediate solution's cost, while optimizing.
+pub fn print_cost<W: Write>(buf: &mut W, cost: u64) -> Result<()> {
+    writeln!(buf, "o {}", cost)
+}
+
+/// Print the result of optimizing, where any solution is an optimal one.
+pub fn print_optimum<W: Write>(buf: &mut W, soln: Option<Vec<l::Literal>>) -> Result<()> {
+    match soln {
+        None => writeln!(buf, "s UNSATISFIABLE"),
+        Some(soln) => {
+            writeln!(buf, "s OPTIMUM FOUND")?;
+            print_values(buf, &soln)
+        }
+    }
+}
+
+/// What a solver said, as printed by `print` or `print_unknown`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    Satisfiable(Vec<l::Literal>),
+    Unsatisfiable,
+    Unknown,
+}
+
+/// A DIMACS Solution Parser, for reading what `print` wrote.
+///
+/// This only reads as many lines as it needs, so it can be used on a stream
+/// with more to come. Comments are skipped, and values can be split over
+/// several `v` lines.
+pub fn parse_solution<R: BufRead>(buf: &mut R) -> Option<Status> {
+    let mut values: Option<Vec<l::Literal>> = None;
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        if buf.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('c') {
+            continue;
+        }
+
+        match &mut values {
+            None => match trimmed {
+                "s SATISFIABLE" => values = Some(vec![]),
+                "s UNSATISFIABLE" => return Some(Status::Unsatisfiable),
+                "s UNKNOWN" => return Some(Status::Unknown),
+                _ => return None,
+            },
+            Some(values) => {
+                let rest = trimmed.strip_prefix('v')?;
+                for token in rest.split_ascii_whitespace() {
+                    match l::Literal::from_dimacs(token.parse().ok()?) {
+                        Some(lit) => values.push(lit),
+                        None => return Some(Status::Satisfiable(std::mem::take(values))),
+                    }
+                }
             }
-            writeln!(buf, "0")
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lit(x: i32) -> l::Literal {
+        l::Literal::from_dimacs(x).unwrap()
+    }
+
+    #[test]
+    fn header_variables() {
+        let cnf = parse_cnf("c unused variables\np cnf 5 2\n1 -2 0\n2 3 0\n".as_bytes()).unwrap();
+        assert_eq!(cnf.variables, 5);
+        assert_eq!(cnf.clauses.len(), 2);
+
+        assert!(parse_cnf("p cnf x 1\n1 0\n".as_bytes()).is_none());
+        // Only the clauses matter here.
+        assert!(parse("p cnf x 1\n1 0\n".as_bytes()).is_some());
+    }
+
+    #[test]
+    fn wcnf() {
+        let input = "c a comment\np wcnf 3 4 10\n10 1 2 0\n3 -1 0\n\
+                     5 -2\n 3 0\n12 3 0\n";
+        let wcnf = parse_wcnf(input.as_bytes()).unwrap();
+        assert_eq!(wcnf.variables, 3);
+        assert_eq!(wcnf.hard.len(), 2);
+        assert_eq!(
+            wcnf.hard[1].iter().copied().collect::<Vec<_>>(),
+            vec![lit(3)]
+        );
+        let soft: Vec<(u64, Vec<l::Literal>)> = wcnf
+            .soft
+            .iter()
+            .map(|(w, c)| (*w, c.iter().copied().collect()))
+            .collect();
+        assert_eq!(soft, vec![(3, vec![lit(-1)]), (5, vec![lit(-2), lit(3)])]);
+
+        // Without a top, everything is soft.
+        let wcnf = parse_wcnf("p wcnf 1 1\n1000 1 0\n".as_bytes()).unwrap();
+        assert!(wcnf.hard.is_empty());
+        assert_eq!(wcnf.soft.len(), 1);
+
+        assert!(parse_wcnf("p wcnf 1 2 10\n1 1 0\n".as_bytes()).is_none());
+        assert!(parse_wcnf("p wcnf 1 1 10\n1 1\n".as_bytes()).is_none());
+        assert!(parse_wcnf("p wcnf 1 1 10\n-1 1 0\n".as_bytes()).is_none());
+        assert!(parse_wcnf("p cnf 1 1\n1 0\n".as_bytes()).is_none());
+        // `h` isn't a weight in the old format.
+        assert!(parse_wcnf("p wcnf 1 1 10\nh 1 0\n".as_bytes()).is_none());
+    }
+
+    #[test]
+    fn headerless_wcnf() {
+        let input = "c a comment\nh 1 2 0\n3 -1 0\n5 -2 4 0\nh 3 0\n";
+        let wcnf = parse_wcnf(input.as_bytes()).unwrap();
+        assert_eq!(wcnf.variables, 4);
+        assert_eq!(wcnf.hard.len(), 2);
+        assert_eq!(
+            wcnf.hard[0].iter().copied().collect::<Vec<_>>(),
+            vec![lit(1), lit(2)]
+        );
+        let soft: Vec<(u64, Vec<l::Literal>)> = wcnf
+            .soft
+            .iter()
+            .map(|(w, c)| (*w, c.iter().copied().collect()))
+            .collect();
+        assert_eq!(soft, vec![(3, vec![lit(-1)]), (5, vec![lit(-2), lit(4)])]);
+
+        // Weights can be bigger than 32 bits.
+        let wcnf = parse_wcnf("10000000000 1 0\n".as_bytes()).unwrap();
+        assert_eq!(wcnf.soft[0].0, 10_000_000_000);
+
+        assert!(parse_wcnf("h 1 2\n".as_bytes()).is_none());
+        assert!(parse_wcnf("x 1 0\n".as_bytes()).is_none());
+        // A header has to come before any clauses.
+        assert!(parse_wcnf("h 1 0\np wcnf 1 1 10\n".as_bytes()).is_none());
+    }
+
+    #[test]
+    fn gcnf() {
+        let input = "c a comment\np gcnf 3 4 3\n{0} 1 2 0\n{2} -1 0\n\
+                     {2} -2\n 3 0\n{1} 3 0\n";
+        let gcnf = parse_gcnf(input.as_bytes()).unwrap();
+        assert_eq!(gcnf.variables, 3);
+        assert_eq!(gcnf.hard.len(), 1);
+        let groups: Vec<Vec<Vec<l::Literal>>> = gcnf
+            .groups
+            .iter()
+            .map(|g| g.iter().map(|c| c.iter().copied().collect()).collect())
+            .collect();
+        assert_eq!(
+            groups,
+            vec![
+                vec![vec![lit(3)]],
+                vec![vec![lit(-1)], vec![lit(-2), lit(3)]],
+                vec![],
+            ]
+        );
+
+        let mut out = vec![];
+        print_groups(&mut out, &gcnf, &[1]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "c groups 2\np gcnf 3 3 2\n{0} 1 2 0\n{2} -1 0\n{2} -2 3 0\n"
+        );
+
+        assert!(parse_gcnf("p gcnf 1 2 1\n{1} 1 0\n".as_bytes()).is_none());
+        assert!(parse_gcnf("p gcnf 1 1 1\n{1} 1\n".as_bytes()).is_none());
+        assert!(parse_gcnf("p gcnf 1 1 1\n{2} 1 0\n".as_bytes()).is_none());
+        assert!(parse_gcnf("p gcnf 1 1 1\n1 1 0\n".as_bytes()).is_none());
+        assert!(parse_gcnf("p cnf 1 1\n1 0\n".as_bytes()).is_none());
+    }
+
+    #[test]
+    fn counting_comments() {
+        let input = "p cnf 3 1\nc p show 1 2 0\nc p show 3 0\nc p weight -1 0.25 0\n\
+                     c p weight 2 3 0\nc ordinary comment\n1 2 0\n";
+        let cnf = parse_annotated_cnf(input.as_bytes()).unwrap();
+        assert_eq!(
+            cnf.show,
+            Some(vec![
+                l::Variable::new(1),
+                l::Variable::new(2),
+                l::Variable::new(3)
+            ])
+        );
+        assert_eq!(cnf.weights, vec![(lit(-1), 0.25), (lit(2), 3.0)]);
+
+        let cnf = parse_annotated_cnf("p cnf 1 1\n1 0\n".as_bytes()).unwrap();
+        assert_eq!(cnf.show, None);
+        assert!(cnf.weights.is_empty());
+
+        let malformed = [
+            "c p show -1 0\np cnf 1 0\n",
+            "c p weight 1 -2 0\np cnf 1 0\n",
+            "c p weight 1 0.5\np cnf 1 0\n",
+        ];
+        for input in &malformed {
+            assert!(parse_annotated_cnf(input.as_bytes()).is_none());
+        }
+
+        // Nothing else reads them, so they're just comments.
+        let cnf = parse_cnf(input.as_bytes()).unwrap();
+        assert_eq!(cnf.show, None);
+        assert!(cnf.weights.is_empty());
+        for input in &malformed {
+            assert!(parse_cnf(input.as_bytes()).is_some());
+            assert!(parse(input.as_bytes()).is_some());
+        }
+    }
+
+    #[test]
+    fn counts() {
+        let mut out = vec![];
+        print_count(&mut out, &BigUint::power_of_two(70), false).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "s SATISFIABLE\nc s type mc\nc s exact arb int 1180591620717411303424\n"
+        );
+
+        let mut out = vec![];
+        print_count(&mut out, &BigUint::zero(), true).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "s UNSATISFIABLE\nc s type pmc\nc s exact arb int 0\n"
+        );
+
+        let mut out = vec![];
+        print_weighted_count(&mut out, 0.001, false).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "s SATISFIABLE\nc s type wmc\nc s log10-estimate -3\nc s exact double prec-sci 1e-3\n"
+        );
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(parse_literals("1 -2 0"), Some(vec![lit(1), lit(-2)]));
+        assert_eq!(parse_literals("0"), Some(vec![]));
+        assert_eq!(parse_literals("1 -2"), None);
+        assert_eq!(parse_literals("1 x 0"), None);
+    }
+
+    #[test]
+    fn solutions() {
+        let mut out = vec![];
+        print(&mut out, Some(vec![lit(1), lit(-2)])).unwrap();
+        print(&mut out, None).unwrap();
+        print_unknown(&mut out).unwrap();
+        out.extend_from_slice(b"c split\ns SATISFIABLE\nv 3\nv -4 0\n");
+
+        let mut buf = out.as_slice();
+        assert_eq!(
+            parse_solution(&mut buf),
+            Some(Status::Satisfiable(vec![lit(1), lit(-2)]))
+        );
+        assert_eq!(parse_solution(&mut buf), Some(Status::Unsatisfiable));
+        assert_eq!(parse_solution(&mut buf), Some(Status::Unknown));
+        assert_eq!(
+            parse_solution(&mut buf),
+            Some(Status::Satisfiable(vec![lit(3), lit(-4)]))
+        );
+        assert_eq!(parse_solution(&mut buf), None);
+    }
+}
@@ -0,0 +1,452 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Solving with several worker processes, on one machine, talking over a Unix
+//! domain socket.
+//!
+//! The coordinator listens on a socket in a directory only its user can get
+//! into, so nobody else can connect to it, and each worker connects to it. The
+//! coordinator then sends the problem as iCNF: a `p inccnf` line and the
+//! clauses. After that, it sends one job at a time, as an iCNF cube (`a 1 -2
+//! 0`), optionally preceded by `c config N` to pick the worker's `N`th
+//! portfolio configuration. The worker replies to each job the same way `sat`
+//! prints answers (`s SATISFIABLE` and a `v` line, `s UNSATISFIABLE` or
+//! `s UNKNOWN`).
+//!
+//! When the coordinator has an answer, it shuts down every connection. Workers
+//! take that as a signal to stop, interrupting any job they are working on.
+
+use super::cdcl;
+use super::clause as cls;
+use super::cube::Cube;
+use super::dimacs;
+use super::literal as lit;
+use super::portfolio;
+
+use std::collections::{HashSet, VecDeque};
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::Shutdown;
+use std::os::unix::fs::DirBuilderExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+type Problem<'a> = &'a [cls::Clause];
+
+/// How long to wait for every worker to connect.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How to split the work between the workers.
+#[derive(Debug, Clone)]
+pub enum Plan {
+    /// Solve each cube, handing a new one to each worker as it finishes.
+    Cubes(Vec<Cube>),
+    /// Give every worker the whole problem, each with a different
+    /// configuration.
+    Portfolio,
+}
+
+// A piece of work for a single worker.
+#[derive(Debug, Clone)]
+struct Job {
+    config: Option<usize>,
+    cube: Cube,
+}
+
+/// Coordinate `workers` workers to solve `clauses`.
+///
+/// Once we are listening, `launch` is called once for each worker with the
+/// socket's path, and should start something that calls `work` with it,
+/// usually a `sat worker` process. Jobs are handed out as workers finish their
+/// last one. If a worker goes away before finishing a job, or sends a model
+/// which doesn't satisfy `clauses`, its job is given to another.
+///
+/// Returns the answer, and a model if it is `Satisfiable`. The answer is
+/// `Unknown` if every worker went away before we found out.
+pub fn coordinate<F>(
+    clauses: Problem,
+    plan: Plan,
+    workers: usize,
+    mut launch: F,
+) -> io::Result<(cdcl::Answer, Vec<lit::Literal>)>
+where
+    F: FnMut(&Path) -> io::Result<()>,
+{
+    let mut queue: VecDeque<Job> = match plan {
+        Plan::Cubes(cubes) => cubes
+            .into_iter()
+            .map(|cube| Job { config: None, cube })
+            .collect(),
+        Plan::Portfolio => (0..workers)
+            .map(|i| Job {
+                config: Some(i),
+                cube: vec![],
+            })
+            .collect(),
+    };
+
+    let streams = {
+        let dir = PrivateDir::new()?;
+        let socket = dir.0.join("socket");
+        let listener = UnixListener::bind(&socket)?;
+        for _ in 0..workers {
+            launch(&socket)?;
+        }
+        accept(&listener, workers)?
+    };
+
+    for stream in &streams {
+        let mut out = io::BufWriter::new(stream);
+        dimacs::print_icnf(&mut out, clauses, &[])?;
+        out.flush()?;
+    }
+
+    let (results, finished) = mpsc::channel();
+    let mut jobs = vec![];
+    let mut handles = vec![];
+    for (id, stream) in streams.iter().enumerate() {
+        let (send, receive) = mpsc::channel::<Job>();
+        jobs.push(Some(send));
+
+        let mut stream = stream.try_clone()?;
+        let results = results.clone();
+        handles.push(thread::spawn(move || {
+            let mut reader = BufReader::new(stream.try_clone()?);
+            for job in receive {
+                if let Some(config) = job.config {
+                    writeln!(stream, "c config {}", config)?;
+                }
+                dimacs::print_cube(&mut stream, &job.cube)?;
+                stream.flush()?;
+
+                let status = dimacs::parse_solution(&mut reader);
+                let gone = status.is_none();
+                if results.send((id, job, status)).is_err() || gone {
+                    break;
+                }
+            }
+            io::Result::Ok(())
+        }));
+    }
+    drop(results);
+
+    let mut idle = vec![true; jobs.len()];
+    let mut outstanding = 0;
+    let mut answer = (cdcl::Answer::Unsatisfiable, vec![]);
+    loop {
+        for (id, send) in jobs.iter().enumerate() {
+            if let (Some(send), true) = (send, idle[id]) {
+                if let Some(job) = queue.pop_front() {
+                    idle[id] = false;
+                    outstanding += 1;
+                    let _ = send.send(job);
+                }
+            }
+        }
+        if outstanding == 0 {
+            break;
+        }
+
+        let (id, job, status) = match finished.recv() {
+            Ok(result) => result,
+            Err(_) => break,
+        };
+        outstanding -= 1;
+        idle[id] = true;
+
+        match status {
+            Some(dimacs::Status::Satisfiable(model)) if satisfies(clauses, &model) => {
+                answer = (cdcl::Answer::Satisfiable, model);
+                break;
+            }
+            Some(dimacs::Status::Satisfiable(_)) => {
+                // We can't trust anything else this worker says.
+                jobs[id] = None;
+                queue.push_front(job);
+            }
+            Some(dimacs::Status::Unsatisfiable) if job.cube.is_empty() => {
+                answer = (cdcl::Answer::Unsatisfiable, vec![]);
+                break;
+            }
+            Some(dimacs::Status::Unsatisfiable) => (),
+            Some(dimacs::Status::Unknown) => answer.0 = cdcl::Answer::Unknown,
+            None => {
+                // The worker went away, so someone else has to do its job.
+                jobs[id] = None;
+                queue.push_front(job);
+            }
+        }
+    }
+
+    if !queue.is_empty() && answer.0 == cdcl::Answer::Unsatisfiable {
+        // Every worker went away with work left to do.
+        answer.0 = cdcl::Answer::Unknown;
+    }
+
+    // Stop any workers still busy, and let the rest go.
+    drop(jobs);
+    for stream in &streams {
+        let _ = stream.shutdown(Shutdown::Both);
+    }
+    for handle in handles {
+        let _ = handle.join();
+    }
+
+    Ok(answer)
+}
+
+// A directory for the socket which only we can get into, so nobody else can
+// connect to it, or put something else in its place. It is removed, with the
+// socket, when dropped.
+struct PrivateDir(PathBuf);
+
+impl PrivateDir {
+    fn new() -> io::Result<PrivateDir> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        loop {
+            let path = env::temp_dir().join(format!(
+                "sat-{}-{}",
+                process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            ));
+            match fs::DirBuilder::new().mode(0o700).create(&path) {
+                Ok(()) => return Ok(PrivateDir(path)),
+                // Someone else has this name, so try the next one.
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for PrivateDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// Does `model` make every clause true?
+fn satisfies(clauses: Problem, model: &[lit::Literal]) -> bool {
+    let model: HashSet<lit::Literal> = model.iter().copied().collect();
+    clauses.iter().all(|c| c.iter().any(|l| model.contains(l)))
+}
+
+// Wait for `workers` connections, giving up if they take too long.
+fn accept(listener: &UnixListener, workers: usize) -> io::Result<Vec<UnixStream>> {
+    listener.set_nonblocking(true)?;
+    let start = Instant::now();
+
+    let mut streams = vec![];
+    while streams.len() < workers {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(false)?;
+                streams.push(stream);
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                if start.elapsed() > CONNECT_TIMEOUT {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "workers did not connect",
+                    ));
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(streams)
+}
+
+// What the coordinator told a worker.
+enum Message {
+    Clause(Vec<lit::Literal>),
+    Config(usize),
+    Cube(Cube),
+}
+
+/// Be a worker: connect to the coordinator at `socket`, and do whatever jobs
+/// it sends until it hangs up.
+pub fn work(socket: &Path) -> io::Result<()> {
+    let mut stream = UnixStream::connect(socket)?;
+    let reader = BufReader::new(stream.try_clone()?);
+    let interrupt = Arc::new(AtomicBool::new(false));
+
+    // Read on another thread, so we notice the coordinator hanging up while
+    // we are solving.
+    let (send, messages) = mpsc::channel();
+    let flag = interrupt.clone();
+    thread::spawn(move || {
+        for line in reader.lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            let line = line.trim();
+
+            let message = if let Some(config) = line.strip_prefix("c config ") {
+                config.trim().parse().ok().map(Message::Config)
+            } else if line.is_empty() || line.starts_with('c') || line.starts_with('p') {
+                continue;
+            } else if let Some(cube) = line.strip_prefix('a') {
+                dimacs::parse_literals(cube).map(Message::Cube)
+            } else {
+                dimacs::parse_literals(line).map(Message::Clause)
+            };
+
+            if message.is_none_or(|m| send.send(m).is_err()) {
+                break;
+            }
+        }
+        flag.store(true, Ordering::Relaxed);
+    });
+
+    let mut clauses = vec![];
+    let mut config = 0;
+    let mut solver: Option<cdcl::Solver> = None;
+
+    for message in messages {
+        match message {
+            Message::Clause(lits) => {
+                if let Some(solver) = solver.as_mut() {
+                    solver.add_clause(lits.iter().copied());
+                }
+                clauses.push(lits);
+            }
+            Message::Config(n) => {
+                if n != config {
+                    config = n;
+                    solver = None;
+                }
+            }
+            Message::Cube(cube) => {
+                let solver = solver.get_or_insert_with(|| {
+                    let mut solver = cdcl::Solver::with_config(portfolio::configuration(config));
+                    solver.set_interrupt(interrupt.clone());
+                    for c in &clauses {
+                        solver.add_clause(c.iter().copied());
+                    }
+                    solver
+                });
+
+                let mut out = io::BufWriter::new(&mut stream);
+                let written = match solver.solve_with(&cube) {
+                    cdcl::Answer::Satisfiable => {
+                        dimacs::print(&mut out, Some(solver.model().to_vec()))
+                    }
+                    cdcl::Answer::Unsatisfiable => dimacs::print(&mut out, None),
+                    cdcl::Answer::Unknown => dimacs::print_unknown(&mut out),
+                }
+                .and_then(|_| out.flush());
+
+                // If the coordinator has hung up, nobody is listening, and
+                // that's fine.
+                match written {
+                    Err(e)
+                        if interrupt.load(Ordering::Relaxed)
+                            || e.kind() == io::ErrorKind::BrokenPipe =>
+                    {
+                        return Ok(())
+                    }
+                    written => written?,
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdcl::tests::pigeons;
+    use crate::cube;
+
+    fn lit(l: i32) -> lit::Literal {
+        lit::Literal::from_dimacs(l).unwrap()
+    }
+
+    // Workers as threads in this process, rather than separate processes.
+    fn run(clauses: Problem, plan: Plan, workers: usize) -> (cdcl::Answer, Vec<lit::Literal>) {
+        let mut threads = vec![];
+        let result = coordinate(clauses, plan, workers, |path| {
+            let path = path.to_path_buf();
+            threads.push(thread::spawn(move || work(&path)));
+            Ok(())
+        })
+        .unwrap();
+
+        for t in threads {
+            t.join().unwrap().unwrap();
+        }
+        result
+    }
+
+    #[test]
+    fn cubes_unsat() {
+        let clauses = pigeons(5);
+        let config = cube::Config {
+            depth: 4,
+            ..cube::Config::default()
+        };
+        let cubes = cube::split(&clauses, &config);
+        assert!(cubes.len() > 3);
+
+        let (answer, _) = run(&clauses, Plan::Cubes(cubes), 3);
+        assert_eq!(answer, cdcl::Answer::Unsatisfiable);
+    }
+
+    #[test]
+    fn cubes_sat() {
+        // The last pigeon doesn't have to go anywhere, so the rest fit.
+        let mut clauses = pigeons(5);
+        clauses.remove(5);
+        let cubes = cube::split(&clauses, &cube::Config::default());
+
+        let (answer, model) = run(&clauses, Plan::Cubes(cubes), 2);
+        assert_eq!(answer, cdcl::Answer::Satisfiable);
+        assert!(clauses.iter().all(|c| c.iter().any(|l| model.contains(l))));
+    }
+
+    #[test]
+    fn portfolio() {
+        let (answer, _) = run(&pigeons(5), Plan::Portfolio, 3);
+        assert_eq!(answer, cdcl::Answer::Unsatisfiable);
+    }
+
+    #[test]
+    fn wrong_model() {
+        // A worker which claims every variable can be false.
+        let clauses: Vec<cls::Clause> = vec![[1, 2].iter().copied().map(lit).collect()];
+        let mut threads = vec![];
+        let (answer, _) = coordinate(&clauses, Plan::Portfolio, 1, |path| {
+            let mut stream = UnixStream::connect(path)?;
+            threads.push(thread::spawn(move || {
+                writeln!(stream, "s SATISFIABLE\nv -1 -2 0")?;
+                io::copy(&mut stream, &mut io::sink()).map(|_| ())
+            }));
+            Ok(())
+        })
+        .unwrap();
+
+        for t in threads {
+            t.join().unwrap().unwrap();
+        }
+        assert_eq!(answer, cdcl::Answer::Unknown);
+    }
+
+    #[test]
+    fn no_cubes() {
+        let (answer, _) = run(&pigeons(2), Plan::Cubes(vec![]), 2);
+        assert_eq!(answer, cdcl::Answer::Unsatisfiable);
+    }
+}
@@ -32,6 +32,7 @@ type Solution = Vec<lit::Literal>;
 /// Returns:
 /// - None if `unsat`
 /// - Some(Solution) if `sat`
+#[allow(clippy::needless_return, clippy::single_match)]
 pub fn satisfiable(clauses: Problem) -> Option<Solution> {
     let mut assumptions = a::AssumptionStore::new();
 
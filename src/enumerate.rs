@@ -0,0 +1,310 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! All-solutions enumeration.
+//!
+//! Each time the solver finds a model, we add a clause blocking it, and carry
+//! on with the same solver, which keeps everything it has learnt. Blocking
+//! clauses only mention the variables being enumerated, so with a projection
+//! they stay short, and every model of the other variables that agrees on the
+//! projection is blocked at once.
+//!
+//! Rather than single models, we can also enumerate cubes: partial
+//! assignments where every way of filling in the rest is a model (or, with a
+//! projection, extends to one). Each model is shrunk to such a cube before it
+//! is blocked, and together they cover every model, as a formula in DNF.
+
+use super::cdcl;
+use super::clause as cls;
+use super::literal as lit;
+
+type Problem<'a> = &'a [cls::Clause];
+
+/// What an `Enumerator` produces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cover {
+    /// Whole models, one at a time.
+    Models,
+    /// Cubes, which may overlap each other.
+    Overlapping,
+    /// Cubes which never overlap, so no model is in two of them. These are
+    /// usually smaller than `Overlapping` cubes, because they have to clash
+    /// with all of the cubes before them.
+    Disjoint,
+}
+
+/// An iterator over the models of a problem, or of their projections onto
+/// some of its variables. Each is different from all the ones before.
+pub struct Enumerator {
+    solver: cdcl::Solver,
+    // The variables we enumerate, in order.
+    show: Vec<lit::Variable>,
+    cover: Cover,
+    // The problem, and with `Cover::Disjoint` the blocking clauses, which
+    // cubes must satisfy.
+    clauses: Vec<Vec<lit::Literal>>,
+    // For each literal (by `code`), the clauses it appears in.
+    occurrences: Vec<Vec<usize>>,
+    done: bool,
+}
+
+impl Enumerator {
+    /// Enumerate assignments to variables `1..=variables`, and any others in
+    /// clauses, or only to `show` if it is given.
+    pub fn new(variables: u32, clauses: Problem, show: Option<&[lit::Variable]>) -> Enumerator {
+        Enumerator::with_cover(variables, clauses, show, Cover::Models)
+    }
+
+    /// Like `new`, but with cubes rather than models, if `cover` says so.
+    pub fn with_cover(
+        variables: u32,
+        clauses: Problem,
+        show: Option<&[lit::Variable]>,
+        cover: Cover,
+    ) -> Enumerator {
+        let mut solver = cdcl::Solver::new();
+        for v in 1..=variables {
+            solver.add_variable(lit::Variable::new(v));
+        }
+        for c in clauses {
+            solver.add_clause(c.iter().copied());
+        }
+
+        let mut show = match show {
+            Some(show) => show.to_vec(),
+            None => (1..=solver.variables() as u32)
+                .map(lit::Variable::new)
+                .collect(),
+        };
+        show.sort_unstable();
+        show.dedup();
+        // Make sure the model covers every variable we'll look at.
+        for v in &show {
+            solver.add_variable(*v);
+        }
+
+        let mut enumerator = Enumerator {
+            solver,
+            show,
+            cover,
+            clauses: vec![],
+            occurrences: vec![],
+            done: false,
+        };
+        if cover != Cover::Models {
+            for c in clauses {
+                enumerator.remember(c.iter().copied().collect());
+            }
+        }
+        enumerator
+    }
+
+    // Keep a clause that cubes have to satisfy.
+    fn remember(&mut self, clause: Vec<lit::Literal>) {
+        let idx = self.clauses.len();
+        for l in &clause {
+            let code = l.code();
+            if code >= self.occurrences.len() {
+                self.occurrences.resize_with(code + 2, Vec::new);
+            }
+            self.occurrences[code].push(idx);
+        }
+        self.clauses.push(clause);
+    }
+
+    // Shrink a model to the literals of the variables we're enumerating which
+    // are needed to satisfy every clause, along with the other variables'
+    // literals, which don't make the cube any bigger.
+    fn shrink(&self, model: &[lit::Literal]) -> Vec<lit::Literal> {
+        // How many literals of the cube each clause is satisfied by.
+        let mut satisfied: Vec<usize> = self
+            .clauses
+            .iter()
+            .map(|c| c.iter().filter(|l| model.binary_search(l).is_ok()).count())
+            .collect();
+
+        let mut cube = vec![];
+        for l in model {
+            if self.show.binary_search(&l.variable()).is_err() {
+                continue;
+            }
+
+            let clauses = self.occurrences.get(l.code()).map_or(&[][..], |o| o);
+            if clauses.iter().all(|ci| satisfied[*ci] > 1) {
+                for ci in clauses {
+                    satisfied[*ci] -= 1;
+                }
+            } else {
+                cube.push(*l);
+            }
+        }
+        cube
+    }
+
+    /// Some statistics from the solver, which has been used for every model.
+    pub fn statistics(&self) -> cdcl::Statistics {
+        self.solver.statistics()
+    }
+}
+
+impl Iterator for Enumerator {
+    type Item = Vec<lit::Literal>;
+
+    fn next(&mut self) -> Option<Vec<lit::Literal>> {
+        if self.done || self.solver.solve() != cdcl::Answer::Satisfiable {
+            self.done = true;
+            return None;
+        }
+
+        let model: Vec<lit::Literal> = match self.cover {
+            Cover::Models => self
+                .solver
+                .model()
+                .iter()
+                .copied()
+                .filter(|l| self.show.binary_search(&l.variable()).is_ok())
+                .collect(),
+            _ => self.shrink(self.solver.model()),
+        };
+
+        // If that was the last one, this will be the empty clause, and the
+        // next call will find out there's nothing left.
+        let blocking: Vec<lit::Literal> = model.iter().map(|l| l.negate()).collect();
+        self.solver.add_clause(blocking.iter().copied());
+        if self.cover == Cover::Disjoint {
+            self.remember(blocking);
+        }
+        Some(model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lit(l: i32) -> lit::Literal {
+        lit::Literal::from_dimacs(l).unwrap()
+    }
+
+    macro_rules! c {
+        [$($e:expr),+ $(,)?] => ({
+            [$($e),+].iter().copied().map(lit).collect::<cls::Clause>()
+        })
+    }
+
+    fn models(enumerator: Enumerator) -> Vec<Vec<i32>> {
+        let mut models: Vec<Vec<i32>> = enumerator
+            .map(|m| {
+                m.iter()
+                    .map(|l| l.to_dimacs().parse::<i32>().unwrap())
+                    .collect()
+            })
+            .collect();
+        models.sort();
+        models
+    }
+
+    #[test]
+    fn all_models() {
+        let clauses = [c![1, 2], c![-1, -2, 3]];
+        let found = models(Enumerator::new(3, &clauses, None));
+        assert_eq!(
+            found,
+            vec![
+                vec![-1, 2, -3],
+                vec![-1, 2, 3],
+                vec![1, -2, -3],
+                vec![1, -2, 3],
+                vec![1, 2, 3],
+            ]
+        );
+    }
+
+    #[test]
+    fn unused_variables() {
+        // 1 XOR 2, with 3 in no clause.
+        let clauses = [c![1, 2], c![-1, -2]];
+        assert_eq!(models(Enumerator::new(3, &clauses, None)).len(), 4);
+        // Variables in clauses count even if the header is too small.
+        assert_eq!(models(Enumerator::new(0, &clauses, None)).len(), 2);
+    }
+
+    #[test]
+    fn projected() {
+        let clauses = [c![1, 2], c![-1, -2, 3]];
+        let show = [lit::Variable::new(3)];
+        assert_eq!(
+            models(Enumerator::new(3, &clauses, Some(&show))),
+            vec![vec![-3], vec![3]]
+        );
+
+        // Projected onto nothing, there is one (empty) model.
+        assert_eq!(
+            models(Enumerator::new(3, &clauses, Some(&[]))),
+            vec![Vec::<i32>::new()]
+        );
+    }
+
+    #[test]
+    fn unsatisfiable() {
+        let clauses = [c![1], c![-1]];
+        assert!(models(Enumerator::new(1, &clauses, None)).is_empty());
+    }
+
+    // Whether a model is in a cube.
+    fn covers(cube: &[lit::Literal], model: &[lit::Literal]) -> bool {
+        cube.iter().all(|l| model.contains(l))
+    }
+
+    #[test]
+    fn cubes() {
+        // (1 OR 2) AND (3 OR 4) has nine models, but a few cubes cover them.
+        let clauses = [c![1, 2], c![3, 4]];
+        let all: Vec<Vec<lit::Literal>> = Enumerator::new(4, &clauses, None).collect();
+        assert_eq!(all.len(), 9);
+
+        for cover in [Cover::Overlapping, Cover::Disjoint] {
+            let cubes: Vec<Vec<lit::Literal>> =
+                Enumerator::with_cover(4, &clauses, None, cover).collect();
+            assert!(cubes.len() < all.len());
+
+            // Every model is in a cube, and every cube only has models in it.
+            for model in &all {
+                let covering = cubes.iter().filter(|c| covers(c, model)).count();
+                assert!(covering >= 1);
+                if cover == Cover::Disjoint {
+                    assert_eq!(covering, 1);
+                }
+            }
+            for cube in &cubes {
+                for c in &clauses {
+                    assert!(c.iter().any(|l| cube.contains(l)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn projected_cubes() {
+        // 3 is free once 1 or 2 is true, and 2 is hidden.
+        let clauses = [c![1, 2], c![-2, 3, 1]];
+        let show = [lit::Variable::new(1), lit::Variable::new(3)];
+        let cubes: Vec<Vec<lit::Literal>> =
+            Enumerator::with_cover(3, &clauses, Some(&show), Cover::Disjoint).collect();
+
+        let projections = models(Enumerator::new(3, &clauses, Some(&show)));
+        assert_eq!(projections.len(), 3);
+        for p in projections {
+            let model: Vec<lit::Literal> = p.into_iter().map(lit).collect();
+            assert_eq!(cubes.iter().filter(|c| covers(c, &model)).count(), 1);
+        }
+    }
+
+    #[test]
+    fn capped() {
+        let enumerator = Enumerator::new(10, &[], None);
+        assert_eq!(enumerator.take(5).count(), 5);
+    }
+}
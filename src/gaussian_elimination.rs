@@ -0,0 +1,274 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use super::assumptions as a;
+use super::cdcl;
+use super::literal as lit;
+use super::xor;
+
+use std::collections::BTreeMap;
+
+/// Propagates a system of XOR constraints together, using Gauss-Jordan
+/// elimination over GF(2).
+///
+/// Each XOR is a row in a bit-matrix, with one column per variable. Whenever
+/// the assignment changes, we eliminate using only the unassigned columns as
+/// pivots. Every resulting row is a sum of the original XORs, so is implied
+/// by them, and:
+/// - if it has no unassigned variables and the wrong parity, it's a conflict;
+/// - if it has one unassigned variable, that variable's value is implied.
+///
+/// This finds things that looking at each XOR on its own never would, e.g.
+/// `x XOR y XOR z` and `x XOR y` together imply `z`.
+///
+/// We redo the elimination from scratch each time, which is simple and keeps
+/// backtracking free.
+pub struct GaussianElimination {
+    columns: Vec<lit::Variable>,
+    column_of: BTreeMap<lit::Variable, usize>,
+    rows: Vec<BitRow>,
+    parities: Vec<bool>,
+    // How much of the trail we've looked at.
+    processed: usize,
+    dirty: bool,
+}
+
+impl GaussianElimination {
+    pub fn new(xors: &[xor::Xor]) -> GaussianElimination {
+        let mut column_of = BTreeMap::new();
+        for x in xors {
+            for v in x.iter() {
+                let next = column_of.len();
+                column_of.entry(v).or_insert(next);
+            }
+        }
+
+        let mut columns = vec![lit::Variable::new(1); column_of.len()];
+        for (v, col) in &column_of {
+            columns[*col] = *v;
+        }
+
+        let rows = xors
+            .iter()
+            .map(|x| {
+                let mut row = BitRow::new(columns.len());
+                for v in x.iter() {
+                    row.flip(column_of[&v]);
+                }
+                row
+            })
+            .collect();
+
+        GaussianElimination {
+            columns,
+            column_of,
+            rows,
+            parities: xors.iter().map(|x| x.parity()).collect(),
+            processed: 0,
+            dirty: true,
+        }
+    }
+
+    fn touches(&self, lits: &[lit::Literal]) -> bool {
+        lits.iter()
+            .any(|l| self.column_of.contains_key(&l.variable()))
+    }
+}
+
+impl cdcl::Propagator for GaussianElimination {
+    fn variables(&self) -> Vec<lit::Variable> {
+        self.columns.clone()
+    }
+
+    fn propagate(&mut self, trail: &cdcl::Trail) -> Result<Vec<cdcl::Reason>, cdcl::Reason> {
+        let assigned = trail.literals();
+        let changed = self.touches(&assigned[self.processed.min(assigned.len())..]);
+        self.processed = assigned.len();
+
+        if !changed && !self.dirty {
+            return Ok(vec![]);
+        }
+        self.dirty = false;
+
+        let values: Vec<a::Assumption> = self.columns.iter().map(|v| trail.get_var(*v)).collect();
+        let mut rows = self.rows.clone();
+        let mut parities = self.parities.clone();
+
+        // Gauss-Jordan elimination, only pivoting on unassigned columns.
+        let mut next_pivot = 0;
+        for (col, value) in values.iter().enumerate() {
+            if *value != a::Assumption::Unknown {
+                continue;
+            }
+
+            let found = (next_pivot..rows.len()).find(|r| rows[*r].get(col));
+            let pivot = match found {
+                Some(r) => r,
+                None => continue,
+            };
+            rows.swap(pivot, next_pivot);
+            parities.swap(pivot, next_pivot);
+
+            for r in 0..rows.len() {
+                if r != next_pivot && rows[r].get(col) {
+                    let (row, pivot_row) = pick_two(&mut rows, r, next_pivot);
+                    row.add(pivot_row);
+                    parities[r] ^= parities[next_pivot];
+                }
+            }
+
+            next_pivot += 1;
+        }
+
+        let mut implied = vec![];
+        for (row, parity) in rows.iter().zip(parities) {
+            // What the unassigned variables in this row have to add up to.
+            let mut parity = parity;
+            let mut unassigned = vec![];
+            let mut reason = vec![];
+
+            for col in row.ones() {
+                let var = self.columns[col];
+                match values[col] {
+                    a::Assumption::Unknown => unassigned.push(var),
+                    a::Assumption::Assume(b) => {
+                        parity ^= b;
+                        reason.push(lit::Literal::new(var, !b));
+                    }
+                }
+            }
+
+            match unassigned.len() {
+                0 if parity => return Err(reason),
+                1 => {
+                    reason.insert(0, lit::Literal::new(unassigned[0], parity));
+                    implied.push(reason);
+                }
+                _ => (),
+            }
+        }
+
+        Ok(implied)
+    }
+
+    fn backtrack(&mut self, trail: &cdcl::Trail, len: usize) {
+        if len < self.processed {
+            if self.touches(&trail.literals()[len..self.processed]) {
+                self.dirty = true;
+            }
+            self.processed = len;
+        }
+    }
+}
+
+// Mutably borrow two different rows at once.
+fn pick_two(rows: &mut [BitRow], a: usize, b: usize) -> (&mut BitRow, &BitRow) {
+    if a < b {
+        let (left, right) = rows.split_at_mut(b);
+        (&mut left[a], &right[0])
+    } else {
+        let (left, right) = rows.split_at_mut(a);
+        (&mut right[0], &left[b])
+    }
+}
+
+// A row of the bit-matrix, packed 64 columns to a word.
+#[derive(Debug, Clone)]
+struct BitRow(Vec<u64>);
+
+impl BitRow {
+    fn new(len: usize) -> BitRow {
+        BitRow(vec![0; len.div_ceil(64)])
+    }
+
+    fn get(&self, col: usize) -> bool {
+        self.0[col / 64] & (1 << (col % 64)) != 0
+    }
+
+    fn flip(&mut self, col: usize) {
+        self.0[col / 64] ^= 1 << (col % 64);
+    }
+
+    fn add(&mut self, other: &BitRow) {
+        for (word, o) in self.0.iter_mut().zip(other.0.iter()) {
+            *word ^= o;
+        }
+    }
+
+    fn ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().enumerate().flat_map(|(w, word)| {
+            (0..64)
+                .filter(move |bit| word & (1 << bit) != 0)
+                .map(move |bit| w * 64 + bit)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lit(l: i32) -> lit::Literal {
+        lit::Literal::from_dimacs(l).unwrap()
+    }
+
+    fn xor(lits: &[i32]) -> xor::Xor {
+        lits.iter().copied().map(lit).collect()
+    }
+
+    fn solve(xors: &[xor::Xor], clauses: &[&[i32]]) -> Option<Vec<lit::Literal>> {
+        let mut solver = cdcl::Solver::new();
+        for c in clauses {
+            solver.add_clause(c.iter().copied().map(lit));
+        }
+        solver.add_propagator(Box::new(GaussianElimination::new(xors)));
+
+        match solver.solve() {
+            cdcl::Answer::Satisfiable => Some(solver.model().to_vec()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn single_xor() {
+        let xors = vec![xor(&[1, 2, 3])];
+        let soln = solve(&xors, &[&[-1], &[-2]]).unwrap();
+
+        assert!(soln.contains(&lit(3)));
+    }
+
+    #[test]
+    fn combined_rows() {
+        // Neither XOR on its own implies anything about 3, but together they
+        // do, before anything is assigned.
+        let xors = vec![xor(&[1, 2, 3]), xor(&[1, -2])];
+        let soln = solve(&xors, &[]).unwrap();
+
+        assert!(soln.contains(&lit(3)));
+        assert!(xors.iter().all(|x| x.is_satisfied(&soln)));
+    }
+
+    #[test]
+    fn inconsistent() {
+        let xors = vec![xor(&[1, 2]), xor(&[2, 3]), xor(&[1, 3])];
+        assert!(solve(&xors, &[]).is_none());
+    }
+
+    #[test]
+    fn parity_chain() {
+        // A chain of XORs which is only unsatisfiable when taken together with
+        // the clauses.
+        let xors: Vec<_> = (1..30).map(|i| xor(&[i, i + 1, 100 + i])).collect();
+        let soln = solve(&xors, &[&[1], &[-30]]).unwrap();
+        assert!(xors.iter().all(|x| x.is_satisfied(&soln)));
+
+        // Now 1 and 30 have to be both equal, and different.
+        let mut xors = xors;
+        xors.push(xor(&[1, -30]));
+        let all_zero: Vec<Vec<i32>> = (101..130).map(|v| vec![-v]).collect();
+        let clauses: Vec<&[i32]> = all_zero.iter().map(|c| c.as_slice()).collect();
+        assert!(solve(&xors, &clauses).is_none());
+    }
+}
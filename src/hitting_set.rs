@@ -0,0 +1,489 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Minimum-cost hitting sets, by branch and bound.
+//!
+//! Given some sets of elements, each element with a cost, find the cheapest
+//! choice of elements that includes at least one from every set.
+//!
+//! We branch on the elements of an unhit set with the fewest choices left:
+//! take the first, or leave it out and take the second, and so on, so the
+//! branches never overlap. The bound comes from a Lagrangian relaxation,
+//! which is about as good as the linear programming one, without needing a
+//! linear programming solver.
+
+/// Sets of elements to hit, which can be added to over time, as the cores of
+/// implicit hitting set MaxSAT are. Each search starts from what the last one
+/// learnt, so adding a few sets and searching again is cheaper than starting
+/// afresh.
+#[derive(Debug, Clone)]
+pub struct HittingSets {
+    costs: Vec<u64>,
+    sets: Vec<Vec<usize>>,
+    // For `Search::bound`, which are as good a place to start as any.
+    multipliers: Vec<f64>,
+    // The last minimum, which only needs a few more elements to hit every set
+    // again.
+    last: Vec<usize>,
+}
+
+impl HittingSets {
+    /// No sets yet, of elements `0..costs.len()`, where element `e` costs
+    /// `costs[e]`.
+    pub fn new(costs: Vec<u64>) -> HittingSets {
+        HittingSets {
+            costs,
+            sets: vec![],
+            multipliers: vec![],
+            last: vec![],
+        }
+    }
+
+    pub fn add(&mut self, set: Vec<usize>) {
+        self.sets.push(set);
+    }
+
+    pub fn sets(&self) -> &[Vec<usize>] {
+        &self.sets
+    }
+
+    /// What a set of elements costs.
+    pub fn cost(&self, elements: &[usize]) -> u64 {
+        elements.iter().map(|e| self.costs[*e]).sum()
+    }
+
+    /// The cheapest set of elements which hits every set, and what it costs,
+    /// or `None` if one of the sets is empty.
+    pub fn minimum(&mut self) -> Option<(u64, Vec<usize>)> {
+        if self.sets.iter().any(|s| s.is_empty()) {
+            return None;
+        }
+
+        let fresh = self.multipliers.is_empty();
+        self.multipliers.resize(self.sets.len(), 0.0);
+        let mut search = Search::new(
+            &self.costs,
+            &self.sets,
+            std::mem::take(&mut self.multipliers),
+        );
+        if fresh {
+            search.split_costs();
+        }
+
+        let greedy = search.greedy();
+        let extended = search.extend(&self.last);
+        search.best = greedy.min(extended);
+        search.branch(0);
+
+        let (cost, mut elements) = search.best;
+        elements.sort_unstable();
+        self.multipliers = search.multipliers;
+        self.last = elements.clone();
+        Some((cost, elements))
+    }
+
+    /// `elements`, and enough more to hit every set, taking the cheapest
+    /// element of each set that isn't hit yet. This is quick, but may cost
+    /// more than it has to.
+    pub fn extend(&self, elements: &[usize]) -> Vec<usize> {
+        let search = Search::new(&self.costs, &self.sets, vec![]);
+        let (_, mut elements) = search.extend(elements);
+        elements.sort_unstable();
+        elements
+    }
+}
+
+struct Search<'a> {
+    costs: &'a [u64],
+    sets: &'a [Vec<usize>],
+    // For each element, the sets it's in.
+    occurrences: Vec<Vec<usize>>,
+    chosen: Vec<bool>,
+    // Elements a branch has already tried, which later branches leave out.
+    banned: Vec<bool>,
+    // For each set, how many of its elements are chosen.
+    hits: Vec<usize>,
+    // The sets, smallest first.
+    by_size: Vec<usize>,
+    // For each set, its multiplier in `bound`.
+    multipliers: Vec<f64>,
+    best: (u64, Vec<usize>),
+}
+
+impl<'a> Search<'a> {
+    fn new(costs: &'a [u64], sets: &'a [Vec<usize>], multipliers: Vec<f64>) -> Search<'a> {
+        let mut occurrences = vec![vec![]; costs.len()];
+        for (idx, s) in sets.iter().enumerate() {
+            for e in s {
+                occurrences[*e].push(idx);
+            }
+        }
+
+        let mut by_size: Vec<usize> = (0..sets.len()).collect();
+        by_size.sort_by_key(|s| sets[*s].len());
+
+        Search {
+            costs,
+            sets,
+            occurrences,
+            chosen: vec![false; costs.len()],
+            banned: vec![false; costs.len()],
+            hits: vec![0; sets.len()],
+            by_size,
+            multipliers,
+            best: (u64::MAX, vec![]),
+        }
+    }
+
+    fn choose(&mut self, e: usize) {
+        self.chosen[e] = true;
+        for s in &self.occurrences[e] {
+            self.hits[*s] += 1;
+        }
+    }
+
+    fn unchoose(&mut self, e: usize) {
+        self.chosen[e] = false;
+        for s in &self.occurrences[e] {
+            self.hits[*s] -= 1;
+        }
+    }
+
+    fn available<'s>(&'s self, set: &'s [usize]) -> impl Iterator<Item = usize> + 's {
+        set.iter().copied().filter(move |e| !self.banned[*e])
+    }
+
+    // Repeatedly take whichever element hits the most unhit sets per unit of
+    // cost, for a first upper bound.
+    fn greedy(&mut self) -> (u64, Vec<usize>) {
+        let mut taken = vec![];
+        let mut cost = 0;
+        while let Some(e) = (0..self.costs.len())
+            .filter(|e| !self.chosen[*e])
+            .map(|e| {
+                let unhit = self.occurrences[e]
+                    .iter()
+                    .filter(|s| self.hits[**s] == 0)
+                    .count() as u64;
+                (e, unhit)
+            })
+            .filter(|(_, unhit)| *unhit > 0)
+            // Compare cost / unhit without dividing.
+            .min_by(|(a, a_unhit), (b, b_unhit)| {
+                let a_cost = self.costs[*a] as u128 * *b_unhit as u128;
+                let b_cost = self.costs[*b] as u128 * *a_unhit as u128;
+                a_cost.cmp(&b_cost)
+            })
+            .map(|(e, _)| e)
+        {
+            self.choose(e);
+            taken.push(e);
+            cost += self.costs[e];
+        }
+
+        for e in &taken {
+            self.unchoose(*e);
+        }
+        (cost, taken)
+    }
+
+    // Each unhit set pays towards its cheapest element, but only what's left
+    // of its cost after the sets before it have paid towards it too, so
+    // nothing is paid for twice. What they pay adds up to a lower bound, and
+    // makes good starting multipliers for `bound`.
+    fn split_costs(&mut self) {
+        let mut left = self.costs.to_vec();
+        // Small sets first, as they have the fewest elements to pay for.
+        for idx in &self.by_size {
+            let s = &self.sets[*idx];
+            if self.hits[*idx] > 0 {
+                continue;
+            }
+            let paid = self.available(s).map(|e| left[e]).min().unwrap_or(0);
+            self.multipliers[*idx] = paid as f64;
+            for e in self.available(s) {
+                left[e] = left[e].saturating_sub(paid);
+            }
+        }
+    }
+
+    // `start`, plus the cheapest element of each set it doesn't hit.
+    fn extend(&self, start: &[usize]) -> (u64, Vec<usize>) {
+        let mut taken = vec![false; self.costs.len()];
+        for e in start {
+            taken[*e] = true;
+        }
+        for idx in &self.by_size {
+            let s = &self.sets[*idx];
+            if !s.iter().any(|e| taken[*e]) {
+                if let Some(e) = s.iter().min_by_key(|e| self.costs[**e]) {
+                    taken[*e] = true;
+                }
+            }
+        }
+
+        let elements: Vec<usize> = (0..self.costs.len()).filter(|e| taken[*e]).collect();
+        let cost = elements.iter().map(|e| self.costs[*e]).sum();
+        (cost, elements)
+    }
+
+    // A lower bound on what it costs to hit the sets that aren't hit yet, from
+    // a Lagrangian relaxation: rather than having to hit every set, we're
+    // paid `multipliers[s]` for each set, and pay it back for every element
+    // we take from the set. Any multipliers give a bound, and we improve them
+    // for a few rounds of subgradient search, starting from wherever the last
+    // call left them.
+    //
+    // Returns the bound rounded up, and as it was, which is what to add to
+    // before rounding anything else. Also returns each element's reduced
+    // cost, its cost less the multipliers of the sets it's in, which taking it
+    // adds to the bound.
+    fn bound(&mut self, cost: u64, rounds: usize) -> (u64, f64, Vec<f64>) {
+        let unhit: Vec<usize> = (0..self.sets.len())
+            .filter(|s| self.hits[*s] == 0)
+            .collect();
+        let free: Vec<usize> = (0..self.costs.len())
+            .filter(|e| !self.chosen[*e] && !self.banned[*e])
+            .collect();
+        // What's left to beat the best solution by.
+        let gap = self.best.0.saturating_sub(cost) as f64;
+
+        let mut reduced = vec![0.0; self.costs.len()];
+        let mut best = (f64::NEG_INFINITY, vec![]);
+        let mut step = 2.0;
+        let mut stalled = 0;
+        for _ in 0..rounds.max(1) {
+            for e in &free {
+                reduced[*e] = self.costs[*e] as f64;
+            }
+            for s in &unhit {
+                for e in self.available(&self.sets[*s]) {
+                    reduced[e] -= self.multipliers[*s];
+                }
+            }
+
+            let bound: f64 = unhit.iter().map(|s| self.multipliers[*s]).sum::<f64>()
+                + free.iter().map(|e| reduced[*e].min(0.0)).sum::<f64>();
+            if bound > best.0 {
+                best = (bound, reduced.clone());
+                stalled = 0;
+            } else {
+                stalled += 1;
+                if stalled == 5 {
+                    step /= 2.0;
+                    stalled = 0;
+                }
+            }
+            if bound >= gap {
+                break;
+            }
+
+            // Take the elements with negative reduced costs, and move the
+            // multipliers towards the sets that leaves unhit (or hit twice).
+            let gradient: Vec<f64> = unhit
+                .iter()
+                .map(|s| {
+                    let taken = self.available(&self.sets[*s]).filter(|e| reduced[*e] < 0.0);
+                    1.0 - taken.count() as f64
+                })
+                .collect();
+            let norm: f64 = gradient.iter().map(|g| g * g).sum();
+            if norm == 0.0 {
+                // The elements we took hit every set exactly once, so the
+                // bound can't get any better.
+                break;
+            }
+            let size = step * (gap - bound).max(1.0) / norm;
+            for (s, g) in unhit.iter().zip(&gradient) {
+                self.multipliers[*s] = (self.multipliers[*s] + size * g).max(0.0);
+            }
+        }
+
+        (round_up(best.0), best.0, best.1)
+    }
+
+    fn branch(&mut self, cost: u64) {
+        // The unhit set with the fewest elements left to choose from.
+        let next = (0..self.sets.len())
+            .filter(|s| self.hits[*s] == 0)
+            .min_by_key(|s| self.available(&self.sets[*s]).count());
+
+        let Some(next) = next else {
+            if cost < self.best.0 {
+                let chosen = (0..self.costs.len()).filter(|e| self.chosen[*e]);
+                self.best = (cost, chosen.collect());
+            }
+            return;
+        };
+
+        let (bound, raw, reduced) = self.bound(cost, ROUNDS);
+        if cost.saturating_add(bound) >= self.best.0 {
+            return;
+        }
+
+        // Elements which would take us past the best we have can be left out
+        // of everything below here.
+        let fixed: Vec<usize> = (0..self.costs.len())
+            .filter(|e| !self.chosen[*e] && !self.banned[*e])
+            .filter(|e| cost.saturating_add(round_up(raw + reduced[*e])) >= self.best.0)
+            .collect();
+        for e in &fixed {
+            self.banned[*e] = true;
+        }
+
+        let mut elements: Vec<usize> = self.available(&self.sets[next]).collect();
+        elements.sort_by(|a, b| reduced[*a].total_cmp(&reduced[*b]));
+
+        for e in &elements {
+            self.choose(*e);
+            self.branch(cost + self.costs[*e]);
+            self.unchoose(*e);
+            self.banned[*e] = true;
+        }
+        for e in elements.iter().chain(&fixed) {
+            self.banned[*e] = false;
+        }
+    }
+}
+
+// Rounds of subgradient search for each bound.
+const ROUNDS: usize = 10;
+
+// Costs are whole numbers, so a bound can be rounded up, but not by so much
+// that rounding errors take it past the true bound.
+fn round_up(bound: f64) -> u64 {
+    (bound - 1e-9 * bound.abs().max(1.0)).ceil().max(0.0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Random;
+
+    // Try every subset of elements.
+    fn brute_force(costs: &[u64], sets: &[Vec<usize>]) -> u64 {
+        (0..1u64 << costs.len())
+            .filter(|bits| sets.iter().all(|s| s.iter().any(|e| bits >> e & 1 == 1)))
+            .map(|bits| {
+                (0..costs.len())
+                    .filter(|e| bits >> e & 1 == 1)
+                    .map(|e| costs[e])
+                    .sum()
+            })
+            .min()
+            .unwrap()
+    }
+
+    fn minimum(costs: &[u64], sets: &[Vec<usize>]) -> Option<(u64, Vec<usize>)> {
+        let mut hs = HittingSets::new(costs.to_vec());
+        for s in sets {
+            hs.add(s.clone());
+        }
+        hs.minimum()
+    }
+
+    #[test]
+    fn trivial() {
+        assert_eq!(minimum(&[1, 2], &[]), Some((0, vec![])));
+        assert_eq!(minimum(&[1, 2], &[vec![0], vec![]]), None);
+        assert_eq!(minimum(&[3, 2], &[vec![0, 1]]), Some((2, vec![1])));
+    }
+
+    #[test]
+    fn greedy_is_wrong() {
+        // Greedy takes 0 first, which hits everything, but 1 and 2 are
+        // cheaper together.
+        let costs = [5, 2, 2];
+        let sets = [vec![0, 1], vec![0, 2]];
+        assert_eq!(minimum(&costs, &sets), Some((4, vec![1, 2])));
+    }
+
+    #[test]
+    fn incremental() {
+        let mut rng = Random::new(6);
+        let n = 12;
+        let costs: Vec<u64> = (0..n).map(|_| rng.below(100) as u64).collect();
+        let mut hs = HittingSets::new(costs.clone());
+        for _ in 0..20 {
+            let s: Vec<usize> = (0..1 + rng.below(4)).map(|_| rng.below(n)).collect();
+            hs.add(s);
+
+            let (cost, elements) = hs.minimum().unwrap();
+            assert_eq!(cost, brute_force(&costs, hs.sets()));
+            assert_eq!(cost, hs.cost(&elements));
+
+            let extended = hs.extend(&elements[..elements.len() / 2]);
+            for s in hs.sets() {
+                assert!(s.iter().any(|e| extended.contains(e)));
+            }
+        }
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = Random::new(5);
+        for _ in 0..100 {
+            let n = 1 + rng.below(10);
+            let costs: Vec<u64> = (0..n).map(|_| rng.below(10) as u64).collect();
+            let sets: Vec<Vec<usize>> = (0..rng.below(12))
+                .map(|_| {
+                    let mut s: Vec<usize> = (0..1 + rng.below(4)).map(|_| rng.below(n)).collect();
+                    s.sort_unstable();
+                    s.dedup();
+                    s
+                })
+                .collect();
+
+            let (cost, elements) = minimum(&costs, &sets).unwrap();
+            assert_eq!(cost, brute_force(&costs, &sets));
+            assert_eq!(cost, elements.iter().map(|e| costs[*e]).sum::<u64>());
+            for s in &sets {
+                assert!(s.iter().any(|e| elements.contains(e)));
+            }
+        }
+    }
+
+    #[test]
+    fn reduced_cost_fixing() {
+        // Fixing elements on a bound that was already rounded up used to ban
+        // one which every optimum needs, and find 27.
+        let costs = [6, 7, 1, 6, 4, 3, 16, 9, 20, 4, 16, 2];
+        let sets = [
+            vec![2, 3, 9],
+            vec![6, 9],
+            vec![1, 2],
+            vec![0, 1, 3, 6],
+            vec![0, 7],
+            vec![3, 5, 7, 9],
+            vec![2, 8, 11],
+            vec![3, 5, 9],
+            vec![3, 5, 6],
+            vec![2, 3, 10],
+            vec![1, 6, 8],
+            vec![6, 8],
+            vec![5, 6, 8, 10],
+            vec![1, 2, 3],
+            vec![0, 1, 2, 11],
+            vec![3, 5, 6],
+            vec![2, 3, 5, 8],
+            vec![0, 4, 6, 11],
+            vec![0, 6, 7, 8],
+        ];
+        assert_eq!(minimum(&costs, &sets).map(|(cost, _)| cost), Some(26));
+
+        // Without free elements, more of them get fixed.
+        let mut rng = Random::new(44);
+        for _ in 0..500 {
+            let n = 4 + rng.below(10);
+            let costs: Vec<u64> = (0..n).map(|_| 1 + rng.below(20) as u64).collect();
+            let sets: Vec<Vec<usize>> = (0..10 + rng.below(15))
+                .map(|_| (0..2 + rng.below(3)).map(|_| rng.below(n)).collect())
+                .collect();
+
+            let (cost, _) = minimum(&costs, &sets).unwrap();
+            assert_eq!(cost, brute_force(&costs, &sets));
+        }
+    }
+}
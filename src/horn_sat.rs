@@ -0,0 +1,154 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use super::clause as cls;
+use super::literal as lit;
+
+use std::collections::VecDeque;
+
+type Problem<'a> = &'a [cls::Clause];
+type Solution = Vec<lit::Literal>;
+
+/// Can `clauses` be handed to `satisfiable`? That is, does every clause have
+/// at most one positive literal?
+pub fn is_horn(clauses: Problem) -> bool {
+    clauses
+        .iter()
+        .all(|c| c.iter().filter(|l| l.polarity()).count() <= 1)
+}
+
+/// Check a set of Horn clauses are satisfiable, in linear time, using the
+/// algorithm from "Linear-time algorithms for testing the satisfiability of
+/// propositional Horn formulae" (Dowling, Gallier, 1984).
+///
+/// A Horn clause `~a OR ~b OR c` is the rule `a AND b => c`. We start with
+/// every variable false, and only make variables true when some rule forces
+/// us to, counting how many of each rule's premises are still false. A rule
+/// with no conclusion whose premises all become true is a contradiction.
+///
+/// Because we only ever make a variable true when we have to, the solution
+/// has as few true variables as possible: it is the minimal model.
+///
+/// Returns:
+/// - None if `unsat`
+/// - Some(Solution) if `sat`, which is the minimal model
+///
+/// Panics if any clause has more than one positive literal.
+pub fn satisfiable(clauses: Problem) -> Option<Solution> {
+    let vars = clauses
+        .iter()
+        .flat_map(|c| c.iter())
+        .map(|l| l.variable().index() as usize)
+        .max()
+        .unwrap_or(0);
+
+    // For each variable, the clauses it is a premise of.
+    let mut premise_of = vec![vec![]; vars + 1];
+    // For each clause, how many premises are not yet true.
+    let mut remaining = vec![0; clauses.len()];
+    let mut conclusions = vec![None; clauses.len()];
+    let mut value = vec![false; vars + 1];
+    let mut worklist = VecDeque::new();
+
+    for (idx, c) in clauses.iter().enumerate() {
+        for l in c.iter() {
+            let v = l.variable().index() as usize;
+            if l.polarity() {
+                assert!(
+                    conclusions[idx].is_none(),
+                    "Horn clauses can have at most one positive literal."
+                );
+                conclusions[idx] = Some(v);
+            } else {
+                premise_of[v].push(idx);
+                remaining[idx] += 1;
+            }
+        }
+
+        if remaining[idx] == 0 {
+            worklist.push_back(idx);
+        }
+    }
+
+    // Each clause in the worklist has all of its premises true.
+    while let Some(idx) = worklist.pop_front() {
+        // A clause can be both a premise and conclusion, e.g. `~a OR a`.
+        if clauses[idx]
+            .iter()
+            .any(|l| l.polarity() && value[l.variable().index() as usize])
+        {
+            continue;
+        }
+
+        let v = conclusions[idx]?;
+
+        value[v] = true;
+        for c in premise_of[v].iter().copied() {
+            remaining[c] -= 1;
+            if remaining[c] == 0 {
+                worklist.push_back(c);
+            }
+        }
+    }
+
+    Some(
+        (1..=vars)
+            .map(|v| lit::Literal::new(lit::Variable::new(v as u32), value[v]))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lit(l: i32) -> lit::Literal {
+        lit::Literal::from_dimacs(l).unwrap()
+    }
+
+    macro_rules! c {
+        [] => ( cls::Clause::new() );
+        [$($e:expr),+ $(,)?] => ({
+            [$($e),+].iter().copied().map(lit).collect::<cls::Clause>()
+        })
+    }
+
+    #[test]
+    fn only_horn() {
+        assert!(is_horn(&[c![-1, -2, 3], c![-4], c![5]]));
+        assert!(!is_horn(&[c![1, 2]]));
+    }
+
+    #[test]
+    fn minimal_model() {
+        // 1, 1 => 2, 2 AND 3 => 4, 5 => 6
+        let clauses = vec![c![1], c![-1, 2], c![-2, -3, 4], c![-5, 6]];
+        let soln = satisfiable(&clauses).unwrap();
+
+        assert_eq!(
+            soln,
+            vec![lit(1), lit(2), lit(-3), lit(-4), lit(-5), lit(-6)]
+        );
+    }
+
+    #[test]
+    fn contradiction() {
+        // 1, 1 => 2, 1 AND 2 => false
+        let clauses = vec![c![1], c![-1, 2], c![-1, -2]];
+        assert!(satisfiable(&clauses).is_none());
+    }
+
+    #[test]
+    fn empty_clause() {
+        assert!(satisfiable(&[c![]]).is_none());
+    }
+
+    #[test]
+    fn tautology() {
+        // 1 => 1 doesn't force anything.
+        let clauses = vec![c![-1, 1], c![-2]];
+        assert_eq!(satisfiable(&clauses).unwrap(), vec![lit(-1), lit(-2)]);
+    }
+}
@@ -8,19 +8,51 @@
 //! It won't be that good, go look somewhere else.
 
 // Core data structures (this may change)
+pub mod cardinality;
 pub mod clause;
 pub mod literal;
+pub mod pseudo_boolean;
+pub mod xor;
 
 // Utilities
 pub mod assumptions;
+pub mod bigint;
+pub mod random;
 
 // Formats
 pub mod dimacs;
+pub mod opb;
+
+// Preprocessing
+pub mod bounded_variable_addition;
 
 // Free Algorithms
+pub mod backbone;
+pub mod cdcl;
+pub mod count;
+pub mod cube;
+#[cfg(unix)]
+pub mod distributed;
 pub mod dpll;
+pub mod enumerate;
+pub mod gaussian_elimination;
+pub mod hitting_set;
+pub mod horn_sat;
+pub mod marco;
+pub mod maxsat;
+pub mod minimal;
+pub mod mus;
+pub mod optimize;
+pub mod portfolio;
 pub mod pure_literal_elimination;
+pub mod two_sat;
 pub mod unit_propagation;
 
+// Incomplete Algorithms
+pub mod ccanr;
+pub mod local_search;
+pub mod probsat;
+pub mod walksat;
+
 #[cfg(test)]
 mod tests {}
@@ -12,14 +12,67 @@
 /// A Variable in a SAT problem.
 ///
 /// This is a symbolic variable that will stand for true or false in a solution.
-#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
 pub struct Variable(u32);
 
+impl Variable {
+    /// Variables are numbered from 1, as they are in DIMACS.
+    pub fn new(index: u32) -> Variable {
+        assert!(index != 0, "Variable 0 is not a valid variable.");
+        Variable(index)
+    }
+
+    pub fn index(&self) -> u32 {
+        self.0
+    }
+}
+
+/// Hands out fresh variables, which are numbered after every variable it has
+/// been told about.
+///
+/// Preprocessing techniques that introduce new variables need these to not
+/// collide with variables in the input.
+#[derive(Debug, Clone)]
+pub struct VariableAllocator {
+    last: u32,
+}
+
+impl Default for VariableAllocator {
+    fn default() -> VariableAllocator {
+        VariableAllocator::new()
+    }
+}
+
+impl VariableAllocator {
+    pub fn new() -> VariableAllocator {
+        VariableAllocator { last: 0 }
+    }
+
+    /// Make sure `var` will never be handed out by `fresh`.
+    pub fn observe(&mut self, var: Variable) {
+        self.last = self.last.max(var.0);
+    }
+
+    /// The largest variable seen or allocated so far, if any.
+    pub fn last(&self) -> Option<Variable> {
+        if self.last == 0 {
+            None
+        } else {
+            Some(Variable(self.last))
+        }
+    }
+
+    pub fn fresh(&mut self) -> Variable {
+        self.last += 1;
+        Variable(self.last)
+    }
+}
+
 /// A Literal in a SAT clause.
 ///
 /// A literal is either a Variable or a Negated Variable. This negation is
 /// represented by `polarity`.
-#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
 pub struct Literal {
     variable: Variable,
     polarity: bool,
@@ -62,6 +115,13 @@ impl Literal {
     pub fn polarity(&self) -> bool {
         self.polarity
     }
+
+    /// An index for this literal, so things can be looked up by literal in a
+    /// `Vec`. A variable's two literals are next to each other, negative
+    /// first.
+    pub fn code(&self) -> usize {
+        2 * self.variable.0 as usize + self.polarity as usize
+    }
 }
 
 #[cfg(test)]
@@ -81,6 +141,15 @@ mod tests {
     }
 
     #[test]
+    fn code() {
+        let v = Variable::new(3);
+        assert_eq!(Literal::new(v, false).code(), 6);
+        assert_eq!(Literal::new(v, true).code(), 7);
+        assert_eq!(Literal::new(v, true).negate().code(), 6);
+    }
+
+    #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn get_polarity() {
         assert_eq!(
             Literal {
@@ -133,4 +202,21 @@ mod tests {
             lit.negate()
         )
     }
+
+    #[test]
+    fn allocate_after_observed() {
+        let mut vars = VariableAllocator::new();
+        assert_eq!(vars.last(), None);
+
+        vars.observe(Variable(4));
+        vars.observe(Variable(2));
+        assert_eq!(vars.last(), Some(Variable(4)));
+
+        assert_eq!(vars.fresh(), Variable(5));
+        assert_eq!(vars.fresh(), Variable(6));
+
+        // Observing something smaller than what we've allocated changes nothing.
+        vars.observe(Variable(3));
+        assert_eq!(vars.fresh(), Variable(7));
+    }
 }
@@ -0,0 +1,53 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use super::clause as cls;
+use super::walksat::Walk;
+
+type Problem<'a> = &'a [cls::Clause];
+
+/// A stochastic local search algorithm, which starts from a random assignment
+/// and flips variables until it finds a solution or runs out of flips.
+///
+/// These can never show a problem is unsatisfiable.
+pub trait LocalSearch {
+    /// Look for a solution to `clauses`. The same configuration, seed
+    /// included, always gives the same walk.
+    fn search(&self, clauses: Problem) -> Walk;
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::literal as lit;
+    use crate::random::Random;
+
+    // A random k-SAT problem, with a solution planted in it so we know it's
+    // satisfiable.
+    pub(crate) fn planted(vars: usize, clauses: usize, k: usize, seed: u64) -> Vec<cls::Clause> {
+        let mut rng = Random::new(seed);
+        let planted: Vec<bool> = (0..=vars).map(|_| rng.chance(0.5)).collect();
+
+        let mut problem = vec![];
+        while problem.len() < clauses {
+            let c: cls::Clause = (0..k)
+                .map(|_| {
+                    let v = 1 + rng.below(vars);
+                    lit::Literal::new(lit::Variable::new(v as u32), rng.chance(0.5))
+                })
+                .collect();
+            if c.iter()
+                .any(|l| planted[l.variable().index() as usize] == l.polarity())
+            {
+                problem.push(c);
+            }
+        }
+        problem
+    }
+
+    pub(crate) fn satisfies(soln: &[lit::Literal], clauses: Problem) -> bool {
+        clauses.iter().all(|c| c.iter().any(|l| soln.contains(l)))
+    }
+}
@@ -3,19 +3,368 @@
 // See COPYRIGHT for details.
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+use sat::backbone;
+use sat::bounded_variable_addition;
+use sat::ccanr;
+use sat::cdcl;
+use sat::count;
+use sat::cube;
 use sat::dimacs;
-use sat::dpll;
+#[cfg(unix)]
+use sat::distributed;
+use sat::enumerate::{Cover, Enumerator};
+use sat::gaussian_elimination::GaussianElimination;
+use sat::horn_sat;
+use sat::literal::{Variable, VariableAllocator};
+use sat::local_search::LocalSearch;
+use sat::marco::{Marco, Subset};
+use sat::maxsat;
+use sat::mus;
+use sat::opb;
+use sat::optimize;
+use sat::portfolio;
+use sat::probsat;
+use sat::pseudo_boolean::PseudoBooleanPropagator;
+use sat::two_sat;
+use sat::walksat;
+use sat::xor;
 
+use std::env;
 use std::io;
+use std::io::Write;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
 // This is written to be as stupid-simple as possible.
+//
+// - `sat` reads a DIMACS CNF problem from stdin.
+// - `sat bva` does the same, but first re-encodes the problem with bounded
+//   variable addition, which can shrink it a lot if it has big at-most-one
+//   constraints.
+// - `sat portfolio [threads] [max-len=n] [max-lbd=n] [max-import=n]` does the
+//   same, but races differently configured solvers against each other, one
+//   per thread (by default, one per core). They share learnt clauses with at
+//   most `max-len` literals, from at most `max-lbd` decision levels (both 2
+//   by default), and each takes at most `max-import` of them at each restart
+//   (1000 by default).
+// - `sat cube [depth]` reads a DIMACS CNF problem from stdin, and splits it
+//   into cubes with lookahead, printing them as iCNF.
+// - `sat cnc [threads]` splits a DIMACS CNF problem into cubes, and solves
+//   them in parallel (cube-and-conquer).
+// - `sat distribute <workers> [portfolio]` reads a DIMACS CNF problem from
+//   stdin, and solves it with several `sat worker` processes, either handing
+//   out cubes or running a portfolio.
+// - `sat count` reads a DIMACS CNF problem from stdin, and counts its
+//   solutions exactly, including over variables in no clause. `c p show`
+//   lines project the count onto some variables, and `c p weight` lines make
+//   it a weighted count.
+// - `sat backbone` reads a DIMACS CNF problem from stdin, and prints the
+//   literals which are true in all of its solutions as a values line.
+// - `sat enumerate [n] [cubes|disjoint]` reads a DIMACS CNF problem from
+//   stdin, and prints its solutions (at most `n` of them) as they are found,
+//   projected onto the variables of any `c p show` lines. With `cubes`, these
+//   are partial solutions, which together cover every solution, and with
+//   `disjoint` they don't overlap either.
+// - `sat maxsat [oll|ihs]` reads a WCNF problem (old or new format) from
+//   stdin, and finds a solution to its hard clauses which minimizes the
+//   weight of the soft clauses it violates, with core-guided search (by
+//   default) or implicit hitting sets.
+// - `sat mus` reads a DIMACS CNF problem from stdin, and if it is
+//   unsatisfiable, prints a minimal unsatisfiable subset of its clauses as
+//   DIMACS CNF, with a comment saying which of the input's clauses they were.
+//   Given a GCNF problem, it does the same for groups of clauses instead,
+//   keeping every clause of group 0.
+// - `sat marco [n] [<seconds>s]` reads a DIMACS CNF or GCNF problem from
+//   stdin, and prints its minimal unsatisfiable subsets (`mus 1 4 0`) and
+//   minimal correction subsets (`mcs 2 0`) of clauses or groups as they are
+//   found, stopping after `n` of them, or when time runs out.
+// - `sat pb` reads an OPB problem from stdin, and finds an optimal solution if
+//   it has an objective.
+// - `sat walksat [seed]`, `sat probsat [seed]` and `sat ccanr [seed]` read a
+//   DIMACS CNF problem from stdin, and look for a solution with local search,
+//   which may give up.
 pub fn main() -> std::io::Result<()> {
+    let command = env::args().nth(1);
+
+    match command.as_deref() {
+        None => solve_cnf(1, &portfolio::Sharing::default(), false),
+        Some("bva") => solve_cnf(1, &portfolio::Sharing::default(), true),
+        Some("portfolio") => {
+            let mut threads = thread::available_parallelism().map_or(1, |n| n.get());
+            let mut sharing = portfolio::Sharing::default();
+            for arg in env::args().skip(2) {
+                let (limit, value) = if let Some(n) = arg.strip_prefix("max-len=") {
+                    (&mut sharing.max_len, n)
+                } else if let Some(n) = arg.strip_prefix("max-lbd=") {
+                    (&mut sharing.max_lbd, n)
+                } else if let Some(n) = arg.strip_prefix("max-import=") {
+                    (&mut sharing.max_import, n)
+                } else {
+                    match arg.parse() {
+                        Ok(n) if n > 0 => threads = n,
+                        _ => {
+                            println!("c Invalid Thread Count");
+                            return Ok(());
+                        }
+                    }
+                    continue;
+                };
+
+                match value.parse() {
+                    Ok(n) => *limit = n,
+                    Err(_) => {
+                        println!("c Invalid Sharing Limit");
+                        return Ok(());
+                    }
+                }
+            }
+            solve_cnf(threads, &sharing, false)
+        }
+        Some("cube") => match env::args().nth(2).map(|s| s.parse()) {
+            None => cube(cube::Config::default().depth),
+            Some(Ok(depth)) => cube(depth),
+            Some(Err(_)) => {
+                println!("c Invalid Depth");
+                Ok(())
+            }
+        },
+        Some("cnc") => match env::args().nth(2).map(|s| s.parse()) {
+            None => cube_and_conquer(thread::available_parallelism().map_or(1, |n| n.get())),
+            Some(Ok(threads)) if threads > 0 => cube_and_conquer(threads),
+            Some(_) => {
+                println!("c Invalid Thread Count");
+                Ok(())
+            }
+        },
+        #[cfg(unix)]
+        Some("distribute") => {
+            let portfolio = env::args().nth(3).as_deref() == Some("portfolio");
+            match env::args().nth(2).map(|s| s.parse()) {
+                Some(Ok(workers)) if workers > 0 => distribute(workers, portfolio),
+                _ => {
+                    println!("c Invalid Worker Count");
+                    Ok(())
+                }
+            }
+        }
+        #[cfg(unix)]
+        Some("worker") => match env::args().nth(2) {
+            Some(socket) => distributed::work(std::path::Path::new(&socket)),
+            None => {
+                println!("c No Socket Given");
+                Ok(())
+            }
+        },
+        Some("count") => count_models(),
+        Some("backbone") => find_backbone(),
+        Some("enumerate") => {
+            let mut limit = usize::MAX;
+            let mut cover = Cover::Models;
+            for arg in env::args().skip(2) {
+                match arg.as_str() {
+                    "cubes" => cover = Cover::Overlapping,
+                    "disjoint" => cover = Cover::Disjoint,
+                    n => match n.parse() {
+                        Ok(n) => limit = n,
+                        Err(_) => {
+                            println!("c Invalid Model Count");
+                            return Ok(());
+                        }
+                    },
+                }
+            }
+            enumerate(limit, cover)
+        }
+        Some("marco") => {
+            let mut limit = usize::MAX;
+            let mut timeout = None;
+            for arg in env::args().skip(2) {
+                if let Some(seconds) = arg.strip_suffix('s') {
+                    // Negative, infinite or NaN limits are no good either.
+                    match seconds.parse().map(Duration::try_from_secs_f64) {
+                        Ok(Ok(seconds)) => timeout = Some(seconds),
+                        _ => {
+                            println!("c Invalid Time Limit");
+                            return Ok(());
+                        }
+                    }
+                } else {
+                    match arg.parse() {
+                        Ok(n) => limit = n,
+                        Err(_) => {
+                            println!("c Invalid Subset Count");
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+            explore_subsets(limit, timeout)
+        }
+        Some("maxsat") => match env::args().nth(2).as_deref() {
+            None | Some("oll") => solve_maxsat(|s, soft, f| maxsat::oll(s, soft, f)),
+            Some("ihs") => solve_maxsat(|s, soft, f| maxsat::ihs(s, soft, f)),
+            Some(other) => {
+                println!("c Unknown MaxSAT Algorithm: {}", other);
+                Ok(())
+            }
+        },
+        Some("mus") => minimal_unsatisfiable_subset(),
+        Some("pb") => solve_pb(),
+        Some("walksat") => solve_local(|seed, _| walksat::Config {
+            seed,
+            ..walksat::Config::default()
+        }),
+        Some("probsat") => solve_local(|seed, clauses| {
+            // The distributions are tuned for problems where every clause is
+            // the same length, so go by the longest.
+            let k = clauses.iter().map(|c| c.len()).max().unwrap_or(0);
+            probsat::Config {
+                distribution: probsat::Distribution::for_clause_length(k),
+                seed,
+                ..probsat::Config::default()
+            }
+        }),
+        Some("ccanr") => solve_local(|seed, _| ccanr::Config {
+            seed,
+            ..ccanr::Config::default()
+        }),
+        Some(other) => {
+            println!("c Unknown Command: {}", other);
+            Ok(())
+        }
+    }
+}
+
+fn solve_cnf(threads: usize, sharing: &portfolio::Sharing, bva: bool) -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+
+    if let Some(clauses) = dimacs::parse(stdin.lock()) {
+        // 2-SAT problems can be solved in linear time, so don't bother with
+        // anything else.
+        if two_sat::is_two_sat(&clauses) {
+            let mut out = stdout.lock();
+            let soln = match two_sat::satisfiable(&clauses) {
+                Ok(soln) => Some(soln),
+                Err(cycle) => {
+                    write!(out, "c Contradiction:")?;
+                    for l in cycle {
+                        write!(out, " {}", l.to_dimacs())?;
+                    }
+                    writeln!(out)?;
+                    None
+                }
+            };
+            return dimacs::print(&mut out, soln);
+        }
+
+        // As can Horn-SAT problems.
+        if horn_sat::is_horn(&clauses) {
+            return dimacs::print(&mut stdout.lock(), horn_sat::satisfiable(&clauses));
+        }
+
+        let mut vars = VariableAllocator::new();
+        for c in &clauses {
+            for l in c.iter() {
+                vars.observe(l.variable());
+            }
+        }
+        let original = vars.last();
+
+        // XORs hidden in the CNF are handled much better by Gaussian
+        // elimination than as clauses.
+        let (xors, clauses) = xor::recover(&clauses);
+
+        // Bounded variable addition only pays off on some problems, like
+        // those with big at-most-one constraints, so it's up to the user.
+        let clauses = if bva {
+            bounded_variable_addition::reencode(&clauses, &mut vars)
+        } else {
+            clauses
+        };
+
+        let setup = |solver: &mut cdcl::Solver| {
+            if let Some(var) = original {
+                solver.add_variable(var);
+            }
+            for c in &clauses {
+                solver.add_clause(c.iter().copied());
+            }
+            if !xors.is_empty() {
+                solver.add_propagator(Box::new(GaussianElimination::new(&xors)));
+            }
+        };
+
+        let (answer, model) = if threads > 1 {
+            let outcome = portfolio::solve(&portfolio::diversify(threads), Some(sharing), setup);
+            if let Some(winner) = outcome.winner {
+                println!("c Winner: {}", winner);
+            }
+            (outcome.answer, outcome.model)
+        } else {
+            let mut solver = cdcl::Solver::new();
+            setup(&mut solver);
+            (solver.solve(), solver.model().to_vec())
+        };
+
+        // Drop any variables we introduced, they mean nothing to the user.
+        let soln = match answer {
+            cdcl::Answer::Satisfiable => Some(
+                model
+                    .into_iter()
+                    .filter(|l| Some(l.variable()) <= original)
+                    .collect(),
+            ),
+            _ => None,
+        };
+
+        dimacs::print(&mut stdout.lock(), soln)?;
+    } else {
+        println!("c No Input Received");
+    }
+
+    Ok(())
+}
+
+fn cube(depth: usize) -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+
+    if let Some(clauses) = dimacs::parse(stdin.lock()) {
+        let config = cube::Config {
+            depth,
+            ..cube::Config::default()
+        };
+        let cubes = cube::split(&clauses, &config);
+        dimacs::print_icnf(&mut stdout.lock(), &clauses, &cubes)?;
+    } else {
+        println!("c No Input Received");
+    }
+
+    Ok(())
+}
+
+fn cube_and_conquer(threads: usize) -> io::Result<()> {
     let stdin = io::stdin();
     let stdout = io::stdout();
 
     if let Some(clauses) = dimacs::parse(stdin.lock()) {
-        let soln = dpll::satisfiable(&clauses);
+        let cubes = cube::split(&clauses, &cube::Config::default());
+        println!("c Cubes: {}", cubes.len());
+
+        let (answer, model) = cube::conquer(&cubes, threads, |solver| {
+            for c in &clauses {
+                solver.add_clause(c.iter().copied());
+            }
+        });
 
+        let soln = match answer {
+            cdcl::Answer::Satisfiable => Some(model),
+            _ => None,
+        };
         dimacs::print(&mut stdout.lock(), soln)?;
     } else {
         println!("c No Input Received");
@@ -23,3 +372,307 @@ pub fn main() -> std::io::Result<()> {
 
     Ok(())
 }
+
+#[cfg(unix)]
+fn distribute(workers: usize, portfolio: bool) -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+
+    if let Some(clauses) = dimacs::parse(stdin.lock()) {
+        let plan = if portfolio {
+            distributed::Plan::Portfolio
+        } else {
+            let cubes = cube::split(&clauses, &cube::Config::default());
+            println!("c Cubes: {}", cubes.len());
+            distributed::Plan::Cubes(cubes)
+        };
+
+        let exe = env::current_exe()?;
+        let mut children = vec![];
+        let result = distributed::coordinate(&clauses, plan, workers, |socket| {
+            let child = process::Command::new(&exe)
+                .arg("worker")
+                .arg(socket)
+                .stdin(process::Stdio::null())
+                .spawn()?;
+            children.push(child);
+            Ok(())
+        });
+
+        // Workers exit when the coordinator hangs up, unless something went
+        // wrong before then.
+        if result.is_err() {
+            for child in children.iter_mut() {
+                let _ = child.kill();
+            }
+        }
+        for mut child in children {
+            child.wait()?;
+        }
+
+        let mut out = stdout.lock();
+        match result? {
+            (cdcl::Answer::Satisfiable, model) => dimacs::print(&mut out, Some(model))?,
+            (cdcl::Answer::Unsatisfiable, _) => dimacs::print(&mut out, None)?,
+            (cdcl::Answer::Unknown, _) => dimacs::print_unknown(&mut out)?,
+        }
+    } else {
+        println!("c No Input Received");
+    }
+
+    Ok(())
+}
+
+fn count_models() -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+
+    if let Some(cnf) = dimacs::parse_annotated_cnf(stdin.lock()) {
+        let show = cnf.show.as_deref();
+        if !cnf.weights.is_empty() {
+            let weight = count::weighted_count(cnf.variables, &cnf.clauses, show, &cnf.weights);
+            dimacs::print_weighted_count(&mut stdout.lock(), weight, show.is_some())?;
+        } else if let Some(show) = show {
+            let n = count::count_projected(cnf.variables, &cnf.clauses, show);
+            dimacs::print_count(&mut stdout.lock(), &n, true)?;
+        } else {
+            let n = count::count(cnf.variables, &cnf.clauses);
+            dimacs::print_count(&mut stdout.lock(), &n, false)?;
+        }
+    } else {
+        println!("c No Input Received");
+    }
+
+    Ok(())
+}
+
+fn find_backbone() -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+
+    if let Some(cnf) = dimacs::parse_cnf(stdin.lock()) {
+        let mut out = stdout.lock();
+        match backbone::backbone(cnf.variables, &cnf.clauses) {
+            Some(backbone) => {
+                writeln!(out, "s SATISFIABLE")?;
+                dimacs::print_values(&mut out, &backbone)?;
+                writeln!(out, "c Backbone: {}", backbone.len())?;
+            }
+            None => writeln!(out, "s UNSATISFIABLE")?,
+        }
+    } else {
+        println!("c No Input Received");
+    }
+
+    Ok(())
+}
+
+fn enumerate(limit: usize, cover: Cover) -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+
+    if let Some(cnf) = dimacs::parse_annotated_cnf(stdin.lock()) {
+        let mut out = stdout.lock();
+        let mut found = 0;
+        let show = cnf.show.as_deref();
+        for model in Enumerator::with_cover(cnf.variables, &cnf.clauses, show, cover).take(limit) {
+            if found == 0 {
+                writeln!(out, "s SATISFIABLE")?;
+            }
+            dimacs::print_values(&mut out, &model)?;
+            out.flush()?;
+            found += 1;
+        }
+
+        if found == 0 && limit > 0 {
+            writeln!(out, "s UNSATISFIABLE")?;
+        }
+        if cover == Cover::Models {
+            writeln!(out, "c Models: {}", found)?;
+        } else {
+            writeln!(out, "c Cubes: {}", found)?;
+        }
+    } else {
+        println!("c No Input Received");
+    }
+
+    Ok(())
+}
+
+type MaxSat = fn(
+    &mut cdcl::Solver,
+    &[(u64, sat::clause::Clause)],
+    &mut dyn FnMut(u64),
+) -> Option<(u64, Vec<sat::literal::Literal>)>;
+
+fn solve_maxsat(algorithm: MaxSat) -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+
+    if let Some(wcnf) = dimacs::parse_wcnf(stdin.lock()) {
+        let mut solver = cdcl::Solver::new();
+        for v in 1..=wcnf.variables {
+            solver.add_variable(Variable::new(v));
+        }
+        for c in &wcnf.hard {
+            solver.add_clause(c.iter().copied());
+        }
+
+        let mut out = stdout.lock();
+        let mut result = Ok(());
+        let best = algorithm(&mut solver, &wcnf.soft, &mut |cost| {
+            // Intermediate results are printed as we go, so flush them.
+            if result.is_ok() {
+                result = dimacs::print_cost(&mut out, cost).and_then(|_| out.flush());
+            }
+        });
+        result?;
+
+        dimacs::print_optimum(&mut out, best.map(|(_, soln)| soln))?;
+    } else {
+        println!("c No Input Received");
+    }
+
+    Ok(())
+}
+
+fn minimal_unsatisfiable_subset() -> io::Result<()> {
+    let mut input = String::new();
+    io::Read::read_to_string(&mut io::stdin(), &mut input)?;
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    if let Some(gcnf) = dimacs::parse_gcnf(input.as_bytes()) {
+        match mus::group_mus(gcnf.variables, &gcnf.hard, &gcnf.groups) {
+            Some(indexes) => dimacs::print_groups(&mut out, &gcnf, &indexes)?,
+            None => writeln!(out, "s SATISFIABLE")?,
+        }
+    } else if let Some(cnf) = dimacs::parse_cnf(input.as_bytes()) {
+        match mus::mus(cnf.variables, &cnf.clauses) {
+            Some(indexes) => dimacs::print_subset(&mut out, cnf.variables, &cnf.clauses, &indexes)?,
+            None => writeln!(out, "s SATISFIABLE")?,
+        }
+    } else {
+        writeln!(out, "c No Input Received")?;
+    }
+
+    Ok(())
+}
+
+fn explore_subsets(limit: usize, timeout: Option<Duration>) -> io::Result<()> {
+    let mut input = String::new();
+    io::Read::read_to_string(&mut io::stdin(), &mut input)?;
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    // Plain CNF is GCNF with a group for each clause.
+    let Some(gcnf) = dimacs::parse_gcnf(input.as_bytes()).or_else(|| {
+        dimacs::parse_cnf(input.as_bytes()).map(|cnf| dimacs::Gcnf {
+            variables: cnf.variables,
+            hard: vec![],
+            groups: cnf.clauses.into_iter().map(|c| vec![c]).collect(),
+        })
+    }) else {
+        writeln!(out, "c No Input Received")?;
+        return Ok(());
+    };
+
+    let mut marco = Marco::new(gcnf.variables, &gcnf.hard, &gcnf.groups);
+    if let Some(timeout) = timeout {
+        let stop = Arc::new(AtomicBool::new(false));
+        marco.set_interrupt(stop.clone());
+        thread::spawn(move || {
+            thread::sleep(timeout);
+            stop.store(true, Ordering::Relaxed);
+        });
+    }
+
+    let (mut muses, mut mcses) = (0, 0);
+    for subset in marco.take(limit) {
+        let (kind, indexes) = match subset {
+            Subset::Unsatisfiable(mus) => {
+                muses += 1;
+                ("mus", mus)
+            }
+            Subset::Correction(mcs) => {
+                mcses += 1;
+                ("mcs", mcs)
+            }
+        };
+        write!(out, "{}", kind)?;
+        for idx in indexes {
+            write!(out, " {}", idx + 1)?;
+        }
+        writeln!(out, " 0")?;
+        out.flush()?;
+    }
+    writeln!(out, "c MUSes: {}", muses)?;
+    writeln!(out, "c MCSes: {}", mcses)?;
+
+    Ok(())
+}
+
+fn solve_pb() -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+
+    if let Some(problem) = opb::parse(stdin.lock()) {
+        let mut solver = cdcl::Solver::new();
+        solver.add_propagator(Box::new(PseudoBooleanPropagator::new(&problem.constraints)));
+
+        if let Some(objective) = problem.objective {
+            let mut out = stdout.lock();
+            let mut result = Ok(());
+            let best = optimize::minimize(&mut solver, &objective, |cost| {
+                // Intermediate results are printed as we go, so flush them.
+                if result.is_ok() {
+                    result = opb::print_cost(&mut out, cost).and_then(|_| out.flush());
+                }
+            });
+            result?;
+
+            return opb::print_optimum(&mut out, best.map(|(_, soln)| soln));
+        }
+
+        let soln = match solver.solve() {
+            cdcl::Answer::Satisfiable => Some(solver.model().to_vec()),
+            _ => None,
+        };
+
+        opb::print(&mut stdout.lock(), soln)?;
+    } else {
+        println!("c No Input Received");
+    }
+
+    Ok(())
+}
+
+fn solve_local<L: LocalSearch>(configure: fn(u64, &[sat::clause::Clause]) -> L) -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+
+    let seed = match env::args().nth(2).map(|s| s.parse()) {
+        None => 0,
+        Some(Ok(seed)) => seed,
+        Some(Err(_)) => {
+            println!("c Invalid Seed");
+            return Ok(());
+        }
+    };
+
+    if let Some(clauses) = dimacs::parse(stdin.lock()) {
+        let walk = configure(seed, &clauses).search(&clauses);
+
+        let mut out = stdout.lock();
+        writeln!(out, "c Flips: {}", walk.flips)?;
+        if walk.is_solution() {
+            dimacs::print(&mut out, Some(walk.assignment))?;
+        } else {
+            dimacs::print_unknown(&mut out)?;
+        }
+    } else {
+        println!("c No Input Received");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,301 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Enumerating every minimal correction subset (MCS) and minimal
+//! unsatisfiable subset (MUS) of a problem, with MARCO.
+//!
+//! An MCS is a set of clauses whose removal makes the rest satisfiable, with
+//! no smaller such set inside it, so each is a different way to fix the
+//! problem. Every MUS shares a clause with every MCS, and the other way
+//! around, which is how MARCO finds them together.
+//!
+//! A map solver has a variable for each clause, and its models are the
+//! subsets we haven't explored yet. We ask it for one, preferring big ones,
+//! and check the clauses in it:
+//! - If they're satisfiable, we add clauses to it until adding any more would
+//!   make it unsatisfiable. What's left out is an MCS, and we block every
+//!   subset of what we kept, as none of them can lead anywhere new.
+//! - Otherwise, we shrink it to a MUS, and block every superset of that.
+//!
+//! Once the map is unsatisfiable, there's nothing left to find. Clauses can
+//! also come in groups, as for `mus::group_mus`.
+
+use super::cdcl;
+use super::clause as cls;
+use super::literal as lit;
+use super::mus;
+
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+/// What a `Marco` finds, as indexes of clauses (or groups), in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Subset {
+    /// A minimal correction subset.
+    Correction(Vec<usize>),
+    /// A minimal unsatisfiable subset.
+    Unsatisfiable(Vec<usize>),
+}
+
+/// An iterator over the MCSes and MUSes of a problem, as they are found.
+pub struct Marco<'a> {
+    map: cdcl::Solver,
+    extractor: mus::Extractor<'a>,
+    groups: usize,
+    done: bool,
+}
+
+impl<'a> Marco<'a> {
+    /// Explore subsets of `groups`, which are always solved with the `hard`
+    /// clauses, over variables `1..=variables` and any others they mention.
+    pub fn new(
+        variables: u32,
+        hard: &'a [cls::Clause],
+        groups: &'a [Vec<cls::Clause>],
+    ) -> Marco<'a> {
+        let mut map = cdcl::Solver::new();
+        for g in 0..groups.len() {
+            map.add_variable(variable(g));
+        }
+
+        Marco {
+            map,
+            extractor: mus::Extractor::new(variables, hard, groups),
+            groups: groups.len(),
+            done: false,
+        }
+    }
+
+    /// Stop early when `flag` is set, after which there are no more subsets.
+    pub fn set_interrupt(&mut self, flag: Arc<AtomicBool>) {
+        self.map.set_interrupt(flag.clone());
+        self.extractor.set_interrupt(flag);
+    }
+
+    // The next subset we haven't explored, as big as the map solver will
+    // easily give us.
+    fn seed(&mut self) -> Option<Vec<usize>> {
+        for g in 0..self.groups {
+            self.map.set_phase(lit::Literal::new(variable(g), true));
+        }
+        if self.map.solve() != cdcl::Answer::Satisfiable {
+            return None;
+        }
+
+        let model = self.map.model();
+        Some(
+            (0..self.groups)
+                .filter(|g| {
+                    model
+                        .binary_search(&lit::Literal::new(variable(*g), true))
+                        .is_ok()
+                })
+                .collect(),
+        )
+    }
+
+    // Add to `kept`, which the extractor's last model satisfies, whatever
+    // keeps it satisfiable, and return what's left out, or `None` if the
+    // solver was interrupted.
+    fn grow(&mut self, kept: Vec<usize>) -> Option<Vec<usize>> {
+        let mut in_kept = vec![false; self.groups];
+        for g in &kept {
+            in_kept[*g] = true;
+        }
+        let mut kept = kept;
+        self.absorb(&mut kept, &mut in_kept);
+
+        for g in 0..self.groups {
+            if in_kept[g] {
+                continue;
+            }
+            kept.push(g);
+            match self.extractor.solve(&kept) {
+                cdcl::Answer::Satisfiable => {
+                    in_kept[g] = true;
+                    self.absorb(&mut kept, &mut in_kept);
+                }
+                cdcl::Answer::Unsatisfiable => {
+                    kept.pop();
+                }
+                cdcl::Answer::Unknown => return None,
+            }
+        }
+
+        Some((0..self.groups).filter(|g| !in_kept[*g]).collect())
+    }
+
+    // Keep every group the last model happens to satisfy too, which saves
+    // checking them one at a time.
+    fn absorb(&self, kept: &mut Vec<usize>, in_kept: &mut [bool]) {
+        for (g, in_kept) in in_kept.iter_mut().enumerate() {
+            if !*in_kept && self.extractor.satisfies(g) {
+                *in_kept = true;
+                kept.push(g);
+            }
+        }
+    }
+}
+
+impl Iterator for Marco<'_> {
+    type Item = Subset;
+
+    fn next(&mut self) -> Option<Subset> {
+        if self.done {
+            return None;
+        }
+
+        let subset = self
+            .seed()
+            .and_then(|seed| match self.extractor.solve(&seed) {
+                cdcl::Answer::Satisfiable => self.grow(seed).map(|mcs| {
+                    // Whatever we explore next has to have one of these.
+                    self.map
+                        .add_clause(mcs.iter().map(|g| lit::Literal::new(variable(*g), true)));
+                    Subset::Correction(mcs)
+                }),
+                cdcl::Answer::Unsatisfiable => self.extractor.shrink(&seed).map(|mus| {
+                    // And it can't have all of these.
+                    self.map
+                        .add_clause(mus.iter().map(|g| lit::Literal::new(variable(*g), false)));
+                    Subset::Unsatisfiable(mus)
+                }),
+                cdcl::Answer::Unknown => None,
+            });
+
+        self.done = subset.is_none();
+        subset
+    }
+}
+
+// The map solver's variable for a group.
+fn variable(group: usize) -> lit::Variable {
+    lit::Variable::new(group as u32 + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Random;
+
+    fn clause(ls: &[i32]) -> cls::Clause {
+        ls.iter()
+            .map(|l| lit::Literal::from_dimacs(*l).unwrap())
+            .collect()
+    }
+
+    fn satisfiable(clauses: &[&cls::Clause]) -> bool {
+        let mut solver = cdcl::Solver::new();
+        for c in clauses {
+            solver.add_clause(c.iter().copied());
+        }
+        solver.solve() == cdcl::Answer::Satisfiable
+    }
+
+    // Every MUS and MCS, by trying every subset.
+    fn brute_force(clauses: &[cls::Clause]) -> (Vec<Vec<usize>>, Vec<Vec<usize>>) {
+        let n = clauses.len();
+        let subset = |bits: u32| -> Vec<usize> { (0..n).filter(|i| bits >> i & 1 == 1).collect() };
+        let sat: Vec<bool> = (0..1u32 << n)
+            .map(|bits| {
+                satisfiable(
+                    &subset(bits)
+                        .iter()
+                        .map(|i| &clauses[*i])
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+
+        let all = (1u32 << n) - 1;
+        let mut muses = vec![];
+        let mut mcses = vec![];
+        for bits in 0..1u32 << n {
+            let others = (0..n).map(|i| bits & !(1 << i));
+            if !sat[bits as usize] && others.clone().all(|b| b == bits || sat[b as usize]) {
+                muses.push(subset(bits));
+            }
+            // Removing `bits` leaves something satisfiable, but removing less
+            // doesn't.
+            if sat[(all & !bits) as usize]
+                && others
+                    .filter(|b| *b != bits)
+                    .all(|b| !sat[(all & !b) as usize])
+            {
+                mcses.push(subset(bits));
+            }
+        }
+        muses.sort();
+        mcses.sort();
+        (muses, mcses)
+    }
+
+    fn explore(clauses: &[cls::Clause]) -> (Vec<Vec<usize>>, Vec<Vec<usize>>) {
+        let groups: Vec<Vec<cls::Clause>> = clauses.iter().map(|c| vec![c.clone()]).collect();
+        let mut muses = vec![];
+        let mut mcses = vec![];
+        for subset in Marco::new(0, &[], &groups) {
+            match subset {
+                Subset::Unsatisfiable(mus) => muses.push(mus),
+                Subset::Correction(mcs) => mcses.push(mcs),
+            }
+        }
+        muses.sort();
+        mcses.sort();
+        (muses, mcses)
+    }
+
+    #[test]
+    fn small() {
+        // 1 and -1 clash, and so do 2 and -2, but 1 OR 2 only needs one of them.
+        let clauses = [clause(&[1]), clause(&[-1]), clause(&[2]), clause(&[-2])];
+        let (muses, mcses) = explore(&clauses);
+        assert_eq!(muses, vec![vec![0, 1], vec![2, 3]]);
+        assert_eq!(mcses, vec![vec![0, 2], vec![0, 3], vec![1, 2], vec![1, 3]]);
+
+        // Satisfiable problems need nothing taken out.
+        let (muses, mcses) = explore(&clauses[..1]);
+        assert!(muses.is_empty());
+        assert_eq!(mcses, vec![Vec::<usize>::new()]);
+    }
+
+    #[test]
+    fn hard_clauses() {
+        let hard = [clause(&[1, 2])];
+        let groups = [vec![clause(&[-1])], vec![clause(&[-2])], vec![clause(&[3])]];
+        let found: Vec<Subset> = Marco::new(3, &hard, &groups).collect();
+        assert_eq!(found.len(), 3);
+        assert!(found.contains(&Subset::Unsatisfiable(vec![0, 1])));
+        assert!(found.contains(&Subset::Correction(vec![0])));
+        assert!(found.contains(&Subset::Correction(vec![1])));
+    }
+
+    #[test]
+    fn interrupted() {
+        let clauses = [vec![clause(&[1])], vec![clause(&[-1])]];
+        let mut marco = Marco::new(1, &[], &clauses);
+        marco.set_interrupt(Arc::new(AtomicBool::new(true)));
+        assert_eq!(marco.next(), None);
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = Random::new(31);
+        for _ in 0..30 {
+            let clauses: Vec<cls::Clause> = (0..10)
+                .map(|_| {
+                    let len = 1 + rng.below(2);
+                    (0..len)
+                        .map(|_| {
+                            let v = lit::Variable::new(1 + rng.below(4) as u32);
+                            lit::Literal::new(v, rng.below(2) == 1)
+                        })
+                        .collect()
+                })
+                .collect();
+            assert_eq!(explore(&clauses), brute_force(&clauses));
+        }
+    }
+}
@@ -0,0 +1,457 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Weighted MaxSAT: satisfy every hard clause, and as many soft clauses as
+//! possible, where leaving a soft clause unsatisfied costs its weight.
+
+use super::cardinality::Totalizer;
+use super::cdcl;
+use super::clause as cls;
+use super::hitting_set;
+use super::literal as lit;
+
+use std::collections::HashMap;
+
+/// What `soln` costs: the total weight of the soft clauses it doesn't satisfy.
+pub fn cost(soft: &[(u64, cls::Clause)], soln: &[lit::Literal]) -> u64 {
+    soft.iter()
+        .filter(|(_, c)| !c.iter().any(|l| soln.contains(l)))
+        .map(|(w, _)| w)
+        .sum()
+}
+
+// Give each soft clause a literal which, when assumed, enforces it, and add up
+// what it costs for each of them to be false. Soft clauses which are a single
+// literal are their own assumption. New variables for the others come from
+// the allocator, which can be used for more, and are numbered after the
+// original variables, which we count too.
+fn relax(
+    solver: &mut cdcl::Solver,
+    soft: &[(u64, cls::Clause)],
+) -> (usize, lit::VariableAllocator, HashMap<lit::Literal, u64>) {
+    for (_, c) in soft {
+        for l in c.iter() {
+            solver.add_variable(l.variable());
+        }
+    }
+    let original = solver.variables();
+    let mut vars = lit::VariableAllocator::new();
+    if original > 0 {
+        vars.observe(lit::Variable::new(original as u32));
+    }
+
+    let mut weights: HashMap<lit::Literal, u64> = HashMap::new();
+    for (w, c) in soft {
+        let assumption = match c.len() {
+            // Always unsatisfied, which `cost` will count.
+            0 => continue,
+            1 => *c.iter().next().unwrap(),
+            _ => {
+                let a = lit::Literal::new(vars.fresh(), true);
+                solver.add_clause(std::iter::once(a.negate()).chain(c.iter().copied()));
+                a
+            }
+        };
+        *weights.entry(assumption).or_insert(0) += w;
+    }
+    weights.retain(|_, w| *w > 0);
+
+    (original, vars, weights)
+}
+
+// The values of the variables we had before `relax`.
+fn solution(solver: &cdcl::Solver, original: usize) -> Vec<lit::Literal> {
+    solver
+        .model()
+        .iter()
+        .copied()
+        .filter(|l| l.variable().index() as usize <= original)
+        .collect()
+}
+
+/// Find a solution to the hard clauses in `solver` which minimizes the cost of
+/// `soft`, with core-guided search (OLL, as in RC2).
+///
+/// Each soft clause gets a literal which, when assumed, enforces it. While
+/// that's unsatisfiable, the solver tells us which assumptions were to blame
+/// (a core), and at least one of them has to go, which costs at least the
+/// smallest weight in the core. We pay that, take it off every weight in the
+/// core, and add a totalizer counting how many of the core are violated, so
+/// that we can assume "at most one" for the same weight, then "at most two"
+/// when that turns up in a core too. Once the assumptions are satisfiable,
+/// we've paid exactly what the solution costs, so it's optimal.
+///
+/// Assumptions are added in strata of decreasing weight, so the cores we find
+/// first are the ones with the heaviest clauses in them. `improved` is called
+/// with each solution's cost as it is found.
+///
+/// Returns:
+/// - None if the hard clauses are `unsat`
+/// - Some((cost, Solution)) for an optimal solution otherwise, with values for
+///   the variables the solver knew about, and those in `soft`
+pub fn oll<F: FnMut(u64)>(
+    solver: &mut cdcl::Solver,
+    soft: &[(u64, cls::Clause)],
+    mut improved: F,
+) -> Option<(u64, Vec<lit::Literal>)> {
+    let (original, mut vars, mut weights) = relax(solver, soft);
+
+    // The outputs of each totalizer, and for assumptions which bound one,
+    // which totalizer and output they are.
+    let mut totalizers: Vec<Vec<lit::Literal>> = vec![];
+    let mut bounds: HashMap<lit::Literal, (usize, usize)> = HashMap::new();
+
+    let mut best: Option<(u64, Vec<lit::Literal>)> = None;
+    let mut stratum = weights.values().copied().max().unwrap_or(0);
+
+    loop {
+        let mut assumptions: Vec<lit::Literal> = weights
+            .iter()
+            .filter(|(_, w)| **w >= stratum)
+            .map(|(l, _)| *l)
+            .collect();
+        // Keep the search deterministic.
+        assumptions.sort_unstable();
+
+        match solver.solve_with(&assumptions) {
+            cdcl::Answer::Satisfiable => {
+                let soln = solution(solver, original);
+                let value = cost(soft, &soln);
+                if best.as_ref().is_none_or(|(b, _)| value < *b) {
+                    improved(value);
+                    best = Some((value, soln));
+                }
+
+                // On to the next stratum, unless this was the last one.
+                match weights.values().copied().filter(|w| *w < stratum).max() {
+                    Some(w) => stratum = w,
+                    None => return best,
+                }
+            }
+            cdcl::Answer::Unsatisfiable => {
+                let core = solver.core().to_vec();
+                if core.is_empty() {
+                    // The hard clauses are unsatisfiable on their own.
+                    return None;
+                }
+
+                let paid = core.iter().map(|l| weights[l]).min().unwrap();
+                for l in &core {
+                    let w = weights.get_mut(l).unwrap();
+                    *w -= paid;
+                    if *w == 0 {
+                        weights.remove(l);
+                    }
+                }
+
+                if core.len() == 1 {
+                    // This one has to be false, whatever else happens.
+                    solver.add_clause([core[0].negate()]);
+                } else {
+                    // At least one of the core is violated, and we'll pay
+                    // again if more than one is.
+                    let violated: Vec<lit::Literal> = core.iter().map(|l| l.negate()).collect();
+                    let totalizer = Totalizer::new(&violated, &mut vars);
+                    for c in &totalizer.clauses {
+                        solver.add_clause(c.iter().copied());
+                    }
+                    solver.add_clause([totalizer.outputs[0]]);
+
+                    totalizers.push(totalizer.outputs);
+                    bound(
+                        &mut weights,
+                        &mut bounds,
+                        &totalizers,
+                        totalizers.len() - 1,
+                        1,
+                        paid,
+                    );
+                }
+
+                // Where we've paid for "at most k" being violated, we'll pay
+                // again for "at most k + 1".
+                for l in &core {
+                    if let Some((t, k)) = bounds.get(l).copied() {
+                        bound(&mut weights, &mut bounds, &totalizers, t, k + 1, paid);
+                    }
+                }
+            }
+            cdcl::Answer::Unknown => return best,
+        }
+    }
+}
+
+// How many greedy hitting sets to try between optimal ones.
+const GREEDY_ROUNDS: usize = 10;
+
+/// Find a solution to the hard clauses in `solver` which minimizes the cost of
+/// `soft`, with implicit hitting sets (IHS).
+///
+/// Like `oll`, each soft clause gets a literal which enforces it, and the
+/// solver finds cores of them, at least one of which has to be false. Rather
+/// than changing the problem, we keep every core, and find the cheapest set
+/// of soft clauses which hits all of them. That's a lower bound on the cost,
+/// and if the solver can satisfy every other soft clause, it's optimal.
+/// Otherwise, we get more cores, leaving out the ones we already have, so
+/// they're disjoint, until it's satisfiable and we have a new upper bound.
+///
+/// This doesn't care how many different weights there are, which `oll`'s
+/// strata do. `improved` is called with each solution's cost as it is found.
+///
+/// Returns:
+/// - None if the hard clauses are `unsat`
+/// - Some((cost, Solution)) for an optimal solution otherwise, with values for
+///   the variables the solver knew about, and those in `soft`
+pub fn ihs<F: FnMut(u64)>(
+    solver: &mut cdcl::Solver,
+    soft: &[(u64, cls::Clause)],
+    mut improved: F,
+) -> Option<(u64, Vec<lit::Literal>)> {
+    let (original, _, weights) = relax(solver, soft);
+    // The hitting set solver works with indexes, so sort the assumptions to
+    // give them some.
+    let mut assumptions: Vec<lit::Literal> = weights.keys().copied().collect();
+    assumptions.sort_unstable();
+    let costs: Vec<u64> = assumptions.iter().map(|l| weights[l]).collect();
+
+    // Every core so far, as indexes into `assumptions`.
+    let mut cores = hitting_set::HittingSets::new(costs);
+    let mut best: Option<(u64, Vec<lit::Literal>)> = None;
+    // Empty soft clauses don't have assumptions, but still cost something.
+    let unavoidable: u64 = soft
+        .iter()
+        .filter(|(_, c)| c.is_empty())
+        .map(|(w, _)| w)
+        .sum();
+
+    loop {
+        let (lower, mut hitting) = cores.minimum()?;
+        if best
+            .as_ref()
+            .is_some_and(|(b, _)| *b <= lower + unavoidable)
+        {
+            return best;
+        }
+
+        // Optimal hitting sets are expensive, so in between them we make do
+        // with greedy ones, until one doesn't give us any more cores.
+        for _ in 0..GREEDY_ROUNDS {
+            let found = cores.sets().len();
+            match disjoint_cores(solver, &assumptions, &hitting, &mut cores) {
+                cdcl::Answer::Satisfiable => {
+                    let soln = solution(solver, original);
+                    let value = cost(soft, &soln);
+                    if best.as_ref().is_none_or(|(b, _)| value < *b) {
+                        improved(value);
+                        best = Some((value, soln));
+                    }
+                    if value <= lower + unavoidable {
+                        return best;
+                    }
+                }
+                cdcl::Answer::Unsatisfiable => return None,
+                cdcl::Answer::Unknown => return best,
+            }
+
+            if cores.sets().len() == found {
+                break;
+            }
+            // Extend the hitting set to the new cores with their cheapest
+            // elements, which keeps it close to the optimal one.
+            hitting = cores.extend(&hitting);
+        }
+    }
+}
+
+// Solve with every assumption except those in `hitting`, and each time that's
+// unsatisfiable, add the core to `cores` and leave it out too, until it's
+// satisfiable. Returns `Unsatisfiable` if the hard clauses are.
+fn disjoint_cores(
+    solver: &mut cdcl::Solver,
+    assumptions: &[lit::Literal],
+    hitting: &[usize],
+    cores: &mut hitting_set::HittingSets,
+) -> cdcl::Answer {
+    let mut left_out = vec![false; assumptions.len()];
+    for e in hitting {
+        left_out[*e] = true;
+    }
+
+    loop {
+        let assumed: Vec<lit::Literal> = (0..assumptions.len())
+            .filter(|e| !left_out[*e])
+            .map(|e| assumptions[e])
+            .collect();
+
+        match solver.solve_with(&assumed) {
+            cdcl::Answer::Unsatisfiable => {
+                let core: Vec<usize> = minimize_core(solver, solver.core().to_vec())
+                    .iter()
+                    .map(|l| assumptions.binary_search(l).unwrap())
+                    .collect();
+                if core.is_empty() {
+                    return cdcl::Answer::Unsatisfiable;
+                }
+                for e in &core {
+                    left_out[*e] = true;
+                }
+                cores.add(core);
+            }
+            answer => return answer,
+        }
+    }
+}
+
+// Drop assumptions from a core for as long as what's left is still a core.
+// Smaller cores make for much better hitting sets.
+fn minimize_core(solver: &mut cdcl::Solver, mut core: Vec<lit::Literal>) -> Vec<lit::Literal> {
+    let mut i = 0;
+    while i < core.len() {
+        let without: Vec<lit::Literal> = core[..i].iter().chain(&core[i + 1..]).copied().collect();
+        if solver.solve_with(&without) == cdcl::Answer::Unsatisfiable {
+            // This might be even smaller than `without`.
+            core = solver.core().to_vec();
+        } else {
+            i += 1;
+        }
+    }
+    core
+}
+
+// Assume at most `k` of totalizer `t`'s inputs are true, for `weight`.
+fn bound(
+    weights: &mut HashMap<lit::Literal, u64>,
+    bounds: &mut HashMap<lit::Literal, (usize, usize)>,
+    totalizers: &[Vec<lit::Literal>],
+    t: usize,
+    k: usize,
+    weight: u64,
+) {
+    if let Some(output) = totalizers[t].get(k) {
+        let assumption = output.negate();
+        *weights.entry(assumption).or_insert(0) += weight;
+        bounds.insert(assumption, (t, k));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Random;
+
+    fn lit(l: i32) -> lit::Literal {
+        lit::Literal::from_dimacs(l).unwrap()
+    }
+
+    fn clause(ls: &[i32]) -> cls::Clause {
+        ls.iter().copied().map(lit).collect()
+    }
+
+    fn solver(hard: &[&[i32]]) -> cdcl::Solver {
+        let mut solver = cdcl::Solver::new();
+        for c in hard {
+            solver.add_clause(c.iter().copied().map(lit));
+        }
+        solver
+    }
+
+    // Try every assignment.
+    fn brute_force(vars: u32, hard: &[cls::Clause], soft: &[(u64, cls::Clause)]) -> Option<u64> {
+        (0..1u64 << vars)
+            .map(|bits| {
+                (1..=vars)
+                    .map(|v| lit::Literal::new(lit::Variable::new(v), bits >> (v - 1) & 1 == 1))
+                    .collect::<Vec<_>>()
+            })
+            .filter(|soln| hard.iter().all(|c| c.iter().any(|l| soln.contains(l))))
+            .map(|soln| cost(soft, &soln))
+            .min()
+    }
+
+    type Algorithm = fn(
+        &mut cdcl::Solver,
+        &[(u64, cls::Clause)],
+        &mut dyn FnMut(u64),
+    ) -> Option<(u64, Vec<lit::Literal>)>;
+
+    const ALGORITHMS: [Algorithm; 2] = [|s, soft, f| oll(s, soft, f), |s, soft, f| ihs(s, soft, f)];
+
+    #[test]
+    fn unsatisfiable() {
+        for algorithm in ALGORITHMS {
+            let mut solver = solver(&[&[1], &[-1]]);
+            assert!(algorithm(&mut solver, &[(1, clause(&[2]))], &mut |_| ()).is_none());
+        }
+    }
+
+    #[test]
+    fn weighted() {
+        // At most one of 1, 2 and 3, but we'd like all of them, and 3 most.
+        let soft = [
+            (2, clause(&[1])),
+            (2, clause(&[2])),
+            (5, clause(&[3])),
+            (1, clause(&[-3, 4])),
+        ];
+        for algorithm in ALGORITHMS {
+            let mut solver = solver(&[&[-1, -2], &[-1, -3], &[-2, -3]]);
+            let mut costs = vec![];
+            let (value, soln) = algorithm(&mut solver, &soft, &mut |c| costs.push(c)).unwrap();
+            assert_eq!(value, 4);
+            assert!(soln.contains(&lit(3)) && soln.contains(&lit(4)));
+            assert_eq!(costs.last(), Some(&4));
+            assert_eq!(soln.len(), 4);
+        }
+    }
+
+    #[test]
+    fn empty_soft_clause() {
+        let soft = [(3, clause(&[])), (1, clause(&[-1]))];
+        for algorithm in ALGORITHMS {
+            let mut solver = solver(&[&[1]]);
+            assert_eq!(algorithm(&mut solver, &soft, &mut |_| ()).unwrap().0, 4);
+        }
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = Random::new(7);
+        let random_clause = |rng: &mut Random, vars: u32| -> cls::Clause {
+            (0..1 + rng.below(3))
+                .map(|_| {
+                    let v = lit::Variable::new(1 + rng.below(vars as usize) as u32);
+                    lit::Literal::new(v, rng.chance(0.5))
+                })
+                .collect()
+        };
+
+        for _ in 0..100 {
+            let vars = 1 + rng.below(8) as u32;
+            let hard: Vec<cls::Clause> = (0..rng.below(8))
+                .map(|_| random_clause(&mut rng, vars))
+                .collect();
+            let soft: Vec<(u64, cls::Clause)> = (0..rng.below(12))
+                .map(|_| (1 + rng.below(5) as u64, random_clause(&mut rng, vars)))
+                .collect();
+
+            for algorithm in ALGORITHMS {
+                let mut solver = cdcl::Solver::new();
+                for v in 1..=vars {
+                    solver.add_variable(lit::Variable::new(v));
+                }
+                for c in &hard {
+                    solver.add_clause(c.iter().copied());
+                }
+
+                let found = algorithm(&mut solver, &soft, &mut |_| ());
+                assert_eq!(found.as_ref().map(|f| f.0), brute_force(vars, &hard, &soft));
+                if let Some((value, soln)) = found {
+                    assert_eq!(cost(&soft, &soln), value);
+                    assert!(hard.iter().all(|c| c.iter().any(|l| soln.contains(l))));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,259 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Smaller witnesses of satisfiability.
+//!
+//! A model pins down every variable, even those no clause cares about. A
+//! prime implicant keeps only the literals that are needed: it satisfies
+//! every clause, but wouldn't without any one of its literals.
+//!
+//! Minimal models make as few variables true as they can, either as few as
+//! any model does (cardinality-minimal), or so that no model makes only some
+//! of them true (subset-minimal), which is cheaper to find.
+
+use super::cdcl;
+use super::clause as cls;
+use super::literal as lit;
+use super::maxsat;
+
+use std::collections::HashSet;
+
+/// Shrink `model` to a prime implicant of `clauses`, keeping the literals in
+/// the order they were in, or `None` if it doesn't satisfy every clause.
+///
+/// Literals are dropped greedily, in order, so this is one prime implicant
+/// of many, and not necessarily the smallest.
+pub fn prime_implicant(
+    clauses: &[cls::Clause],
+    model: &[lit::Literal],
+) -> Option<Vec<lit::Literal>> {
+    let in_model: HashSet<lit::Literal> = model.iter().copied().collect();
+    // How many literals of the implicant each clause is satisfied by.
+    let mut satisfied: Vec<usize> = clauses
+        .iter()
+        .map(|c| c.iter().filter(|l| in_model.contains(l)).count())
+        .collect();
+    if satisfied.contains(&0) {
+        return None;
+    }
+
+    let mut occurrences: Vec<Vec<usize>> = vec![];
+    for (idx, c) in clauses.iter().enumerate() {
+        for l in c.iter().filter(|l| in_model.contains(l)) {
+            let code = l.code();
+            if code >= occurrences.len() {
+                occurrences.resize_with(code + 2, Vec::new);
+            }
+            occurrences[code].push(idx);
+        }
+    }
+
+    // Counts only go down, so once a literal is the only one left to satisfy
+    // some clause, it stays that way.
+    let mut implicant = vec![];
+    for l in model {
+        let clauses = occurrences.get(l.code()).map_or(&[][..], |o| o);
+        if clauses.iter().all(|ci| satisfied[*ci] > 1) {
+            for ci in clauses {
+                satisfied[*ci] -= 1;
+            }
+        } else {
+            implicant.push(*l);
+        }
+    }
+    Some(implicant)
+}
+
+/// A model of `clauses`, over variables `1..=variables` and any others they
+/// mention, with as few true variables as any model, or `None` if they are
+/// unsatisfiable.
+pub fn cardinality_minimal(variables: u32, clauses: &[cls::Clause]) -> Option<Vec<lit::Literal>> {
+    let mut solver = solver(variables, clauses);
+    // Each true variable costs one.
+    let soft: Vec<(u64, cls::Clause)> = (1..=solver.variables() as u32)
+        .map(|v| {
+            (
+                1,
+                std::iter::once(lit::Literal::new(lit::Variable::new(v), false)).collect(),
+            )
+        })
+        .collect();
+    maxsat::oll(&mut solver, &soft, |_| ()).map(|(_, model)| model)
+}
+
+/// A model of `clauses`, over variables `1..=variables` and any others they
+/// mention, whose true variables include those of no other model, or `None`
+/// if they are unsatisfiable.
+///
+/// Each time we find a model, we look for another which keeps every false
+/// variable false, and makes at least one of the true ones false, until
+/// there isn't one.
+pub fn subset_minimal(variables: u32, clauses: &[cls::Clause]) -> Option<Vec<lit::Literal>> {
+    let mut solver = solver(variables, clauses);
+    let original = solver.variables();
+    let mut vars = lit::VariableAllocator::new();
+    if original > 0 {
+        vars.observe(lit::Variable::new(original as u32));
+    }
+
+    if solver.solve() != cdcl::Answer::Satisfiable {
+        return None;
+    }
+    let mut model: Vec<lit::Literal> = solver.model().to_vec();
+
+    loop {
+        let (true_lits, false_lits): (Vec<lit::Literal>, Vec<lit::Literal>) =
+            model.iter().partition(|l| l.polarity());
+        // Only while `active` is assumed, one of the true variables has to
+        // be false.
+        let active = lit::Literal::new(vars.fresh(), true);
+        solver.add_clause(
+            std::iter::once(active.negate()).chain(true_lits.iter().map(|l| l.negate())),
+        );
+
+        let assumptions: Vec<lit::Literal> = std::iter::once(active).chain(false_lits).collect();
+        let answer = solver.solve_with(&assumptions);
+        solver.add_clause([active.negate()]);
+        if answer != cdcl::Answer::Satisfiable {
+            return Some(model);
+        }
+
+        model = solver
+            .model()
+            .iter()
+            .copied()
+            .filter(|l| l.variable().index() as usize <= original)
+            .collect();
+    }
+}
+
+fn solver(variables: u32, clauses: &[cls::Clause]) -> cdcl::Solver {
+    let mut solver = cdcl::Solver::new();
+    for v in 1..=variables {
+        solver.add_variable(lit::Variable::new(v));
+    }
+    for c in clauses {
+        solver.add_clause(c.iter().copied());
+    }
+    solver
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dpll;
+    use crate::random::Random;
+
+    fn lit(l: i32) -> lit::Literal {
+        lit::Literal::from_dimacs(l).unwrap()
+    }
+
+    fn clause(ls: &[i32]) -> cls::Clause {
+        ls.iter().copied().map(lit).collect()
+    }
+
+    fn satisfies(clauses: &[cls::Clause], lits: &[lit::Literal]) -> bool {
+        clauses.iter().all(|c| c.iter().any(|l| lits.contains(l)))
+    }
+
+    fn trues(model: &[lit::Literal]) -> Vec<lit::Literal> {
+        model.iter().copied().filter(|l| l.polarity()).collect()
+    }
+
+    fn random_clauses(rng: &mut Random, vars: usize) -> Vec<cls::Clause> {
+        (0..4 + rng.below(16))
+            .map(|_| {
+                let len = 1 + rng.below(3);
+                (0..len)
+                    .map(|_| {
+                        let v = lit::Variable::new(1 + rng.below(vars) as u32);
+                        lit::Literal::new(v, rng.below(2) == 1)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn implicant() {
+        let clauses = [clause(&[1, 2]), clause(&[-1, 3]), clause(&[2, 3])];
+        let model = [lit(1), lit(2), lit(3), lit(-4)];
+        // 1 goes, as 2 covers the first clause, then 2 and 3 are needed.
+        assert_eq!(
+            prime_implicant(&clauses, &model),
+            Some(vec![lit(2), lit(3)])
+        );
+
+        assert_eq!(prime_implicant(&clauses, &[lit(1), lit(-3)]), None);
+    }
+
+    #[test]
+    fn implicants_are_prime() {
+        let mut rng = Random::new(53);
+        for _ in 0..100 {
+            let clauses = random_clauses(&mut rng, 8);
+            let Some(model) = dpll::satisfiable(&clauses) else {
+                continue;
+            };
+
+            let implicant = prime_implicant(&clauses, &model).unwrap();
+            assert!(satisfies(&clauses, &implicant));
+            for l in &implicant {
+                let without: Vec<lit::Literal> =
+                    implicant.iter().copied().filter(|m| m != l).collect();
+                assert!(!satisfies(&clauses, &without));
+            }
+        }
+    }
+
+    #[test]
+    fn minimal_models() {
+        // 1 alone does it, and so do 2 and 3 together.
+        let clauses = [clause(&[1, 2]), clause(&[1, 3])];
+        assert_eq!(
+            trues(&cardinality_minimal(3, &clauses).unwrap()),
+            vec![lit(1)]
+        );
+        let minimal = trues(&subset_minimal(3, &clauses).unwrap());
+        assert!(minimal == vec![lit(1)] || minimal == vec![lit(2), lit(3)]);
+
+        let unsatisfiable = [clause(&[1]), clause(&[-1])];
+        assert_eq!(cardinality_minimal(1, &unsatisfiable), None);
+        assert_eq!(subset_minimal(1, &unsatisfiable), None);
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = Random::new(59);
+        let vars = 6;
+        for _ in 0..100 {
+            let clauses = random_clauses(&mut rng, vars);
+            let models: Vec<Vec<lit::Literal>> = (0..1u32 << vars)
+                .map(|bits| {
+                    (1..=vars as u32)
+                        .map(|v| lit::Literal::new(lit::Variable::new(v), bits >> (v - 1) & 1 == 1))
+                        .collect::<Vec<_>>()
+                })
+                .filter(|m| satisfies(&clauses, m))
+                .collect();
+            let fewest = models.iter().map(|m| trues(m).len()).min();
+
+            let cardinality = cardinality_minimal(vars as u32, &clauses);
+            assert_eq!(cardinality.as_ref().map(|m| trues(m).len()), fewest);
+
+            let subset = subset_minimal(vars as u32, &clauses);
+            assert_eq!(subset.is_some(), fewest.is_some());
+            if let Some(subset) = subset {
+                assert!(satisfies(&clauses, &subset));
+                // No other model's true variables are a strict subset.
+                let subset = trues(&subset);
+                for m in &models {
+                    let m = trues(m);
+                    assert!(m.len() >= subset.len() || !m.iter().all(|l| subset.contains(l)));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,368 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Minimal unsatisfiable subsets (MUSes), which explain why a problem is
+//! unsatisfiable: they are unsatisfiable, but leaving out any one of their
+//! clauses makes them satisfiable.
+//!
+//! Each clause gets a selector literal which, when assumed, enforces it, so
+//! one solver can try any subset of the clauses. We start from everything,
+//! and try leaving out each clause in turn (deletion-based search):
+//! - If what's left is still unsatisfiable, the clause goes, and so does
+//!   anything else that isn't in the solver's core (clause-set refinement).
+//! - Otherwise, the clause is needed, and the model we got falsifies only it.
+//!   Flipping one of its variables might move that to just one other clause,
+//!   which is then needed too, and so on (model rotation), which saves us
+//!   calling the solver for it.
+//!
+//! Clauses can also come in groups, which are left out or kept together, on
+//! top of hard clauses which are always kept. Then it's groups that get
+//! selectors, and a group MUS is a minimal set of them.
+
+use super::cdcl;
+use super::clause as cls;
+use super::literal as lit;
+
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+/// The indexes of a minimal unsatisfiable subset of `clauses`, over variables
+/// `1..=variables` and any others they mention, in order, or `None` if they
+/// are satisfiable.
+pub fn mus(variables: u32, clauses: &[cls::Clause]) -> Option<Vec<usize>> {
+    let groups: Vec<Vec<cls::Clause>> = clauses.iter().map(|c| vec![c.clone()]).collect();
+    group_mus(variables, &[], &groups)
+}
+
+/// Like `mus`, but for groups of clauses, which are left out or kept
+/// together: the indexes of a minimal set of `groups` which, along with every
+/// `hard` clause, are unsatisfiable. This is empty if the hard clauses are
+/// unsatisfiable on their own.
+pub fn group_mus(
+    variables: u32,
+    hard: &[cls::Clause],
+    groups: &[Vec<cls::Clause>],
+) -> Option<Vec<usize>> {
+    let all: Vec<usize> = (0..groups.len()).collect();
+    Extractor::new(variables, hard, groups).shrink(&all)
+}
+
+/// A solver for the hard clauses and any subset of the groups, which can
+/// shrink unsatisfiable subsets to MUSes, and be used again.
+pub(crate) struct Extractor<'a> {
+    solver: cdcl::Solver,
+    groups: &'a [Vec<cls::Clause>],
+    clauses: Vec<(Option<usize>, &'a cls::Clause)>,
+    selectors: Vec<lit::Literal>,
+    // For each literal (by `code`), the clauses it appears in.
+    occurrences: Vec<Vec<usize>>,
+    // The groups which are still unsatisfiable together, in order.
+    left: Vec<usize>,
+    necessary: Vec<bool>,
+}
+
+impl<'a> Extractor<'a> {
+    pub(crate) fn new(
+        variables: u32,
+        hard: &'a [cls::Clause],
+        groups: &'a [Vec<cls::Clause>],
+    ) -> Extractor<'a> {
+        let mut solver = cdcl::Solver::new();
+        for v in 1..=variables {
+            solver.add_variable(lit::Variable::new(v));
+        }
+        for c in hard.iter().chain(groups.iter().flatten()) {
+            for l in c.iter() {
+                solver.add_variable(l.variable());
+            }
+        }
+
+        let mut vars = lit::VariableAllocator::new();
+        if solver.variables() > 0 {
+            vars.observe(lit::Variable::new(solver.variables() as u32));
+        }
+        for c in hard {
+            solver.add_clause(c.iter().copied());
+        }
+        // These are fresh, so in order, which lets us binary search them.
+        let selectors: Vec<lit::Literal> = groups
+            .iter()
+            .map(|g| {
+                let s = lit::Literal::new(vars.fresh(), true);
+                for c in g {
+                    solver.add_clause(std::iter::once(s.negate()).chain(c.iter().copied()));
+                }
+                s
+            })
+            .collect();
+
+        // Every clause, with the group it's in, if it isn't hard.
+        let clauses: Vec<(Option<usize>, &cls::Clause)> = hard
+            .iter()
+            .map(|c| (None, c))
+            .chain(
+                groups
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(g, cs)| cs.iter().map(move |c| (Some(g), c))),
+            )
+            .collect();
+        let mut occurrences: Vec<Vec<usize>> = vec![vec![]; 2 * (solver.variables() + 1)];
+        for (idx, (_, c)) in clauses.iter().enumerate() {
+            for l in c.iter() {
+                occurrences[l.code()].push(idx);
+            }
+        }
+
+        Extractor {
+            solver,
+            groups,
+            clauses,
+            selectors,
+            occurrences,
+            left: vec![],
+            necessary: vec![],
+        }
+    }
+
+    pub(crate) fn set_interrupt(&mut self, flag: Arc<AtomicBool>) {
+        self.solver.set_interrupt(flag);
+    }
+
+    /// Solve the hard clauses with just `groups`.
+    pub(crate) fn solve(&mut self, groups: &[usize]) -> cdcl::Answer {
+        let assumptions: Vec<lit::Literal> = groups.iter().map(|g| self.selectors[*g]).collect();
+        self.solver.solve_with(&assumptions)
+    }
+
+    /// Whether the model from the last satisfiable `solve` satisfies a group,
+    /// whether it was one of those solved with or not.
+    pub(crate) fn satisfies(&self, group: usize) -> bool {
+        let model = self.solver.model();
+        self.groups[group]
+            .iter()
+            .all(|c| c.iter().any(|l| model.binary_search(l).is_ok()))
+    }
+
+    /// A MUS of `groups`, in order, or `None` if they are satisfiable, or the
+    /// solver was interrupted.
+    pub(crate) fn shrink(&mut self, groups: &[usize]) -> Option<Vec<usize>> {
+        if self.solve(groups) != cdcl::Answer::Unsatisfiable {
+            return None;
+        }
+        self.necessary = vec![false; self.groups.len()];
+        self.refine();
+
+        // Everything before `pos` is needed, and each step either finds that
+        // the group at `pos` is too, or leaves it out.
+        let mut pos = 0;
+        while pos < self.left.len() {
+            let idx = self.left[pos];
+            if self.necessary[idx] {
+                pos += 1;
+                continue;
+            }
+
+            let without: Vec<usize> = self.left.iter().copied().filter(|i| *i != idx).collect();
+            match self.solve(&without) {
+                // Needed groups are in every core, so they all stay before
+                // `pos`.
+                cdcl::Answer::Unsatisfiable => self.refine(),
+                cdcl::Answer::Satisfiable => {
+                    self.necessary[idx] = true;
+                    self.rotate(idx);
+                    pos += 1;
+                }
+                cdcl::Answer::Unknown => return None,
+            }
+        }
+
+        Some(std::mem::take(&mut self.left))
+    }
+
+    // After an unsatisfiable `solve`, keep only the groups in the core.
+    fn refine(&mut self) {
+        self.left = self
+            .solver
+            .core()
+            .iter()
+            .map(|s| self.selectors.binary_search(s).unwrap())
+            .collect();
+        self.left.sort_unstable();
+    }
+
+    // The solver's model satisfies every hard clause, and every group left but
+    // `start`, which is needed. Flip each variable of its falsified clauses in
+    // turn, and if that satisfies it, but falsifies just one other group,
+    // that's needed too, and we carry on from it.
+    fn rotate(&mut self, start: usize) {
+        let mut values = vec![false; self.solver.variables() + 1];
+        for l in self.solver.model() {
+            values[l.variable().index() as usize] = l.polarity();
+        }
+        let mut left = vec![false; self.groups.len()];
+        for g in &self.left {
+            left[*g] = true;
+        }
+
+        let mut stack = vec![(start, values)];
+        while let Some((g, mut values)) = stack.pop() {
+            let falsified: Vec<&cls::Clause> = self.groups[g]
+                .iter()
+                .filter(|c| !satisfied(&values, c))
+                .collect();
+            for l in falsified.iter().flat_map(|c| c.iter()) {
+                let v = l.variable().index() as usize;
+                values[v] = !values[v];
+
+                // Only clauses with the literal that just became false can
+                // have become falsified.
+                let mut others: Vec<Option<usize>> = self.occurrences[l.negate().code()]
+                    .iter()
+                    .map(|c| self.clauses[*c])
+                    .filter(|(o, c)| o.is_none_or(|o| left[o]) && !satisfied(&values, c))
+                    .map(|(o, _)| o)
+                    .collect();
+                others.sort_unstable();
+                others.dedup();
+                if let [Some(other)] = others[..] {
+                    if !self.necessary[other]
+                        && other != g
+                        && self.groups[g].iter().all(|c| satisfied(&values, c))
+                    {
+                        self.necessary[other] = true;
+                        stack.push((other, values.clone()));
+                    }
+                }
+
+                values[v] = !values[v];
+            }
+        }
+    }
+}
+
+fn satisfied(values: &[bool], clause: &cls::Clause) -> bool {
+    clause.iter().any(|l| value(values, *l))
+}
+
+fn value(values: &[bool], l: lit::Literal) -> bool {
+    values[l.variable().index() as usize] == l.polarity()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Random;
+
+    fn clause(ls: &[i32]) -> cls::Clause {
+        ls.iter()
+            .map(|l| lit::Literal::from_dimacs(*l).unwrap())
+            .collect()
+    }
+
+    fn satisfiable(clauses: &[cls::Clause]) -> bool {
+        let mut solver = cdcl::Solver::new();
+        for c in clauses {
+            solver.add_clause(c.iter().copied());
+        }
+        solver.solve() == cdcl::Answer::Satisfiable
+    }
+
+    // Unsatisfiable, but satisfiable without any one of its groups.
+    fn is_mus(hard: &[cls::Clause], groups: &[Vec<cls::Clause>], indexes: &[usize]) -> bool {
+        let subset = |skip: Option<usize>| -> Vec<cls::Clause> {
+            let kept = indexes.iter().filter(|i| Some(**i) != skip);
+            hard.iter()
+                .chain(kept.flat_map(|i| &groups[*i]))
+                .cloned()
+                .collect()
+        };
+        !satisfiable(&subset(None)) && indexes.iter().all(|i| satisfiable(&subset(Some(*i))))
+    }
+
+    fn random_clauses(rng: &mut Random, vars: usize, n: usize) -> Vec<cls::Clause> {
+        (0..n)
+            .map(|_| {
+                let len = 1 + rng.below(3);
+                (0..len)
+                    .map(|_| {
+                        let v = lit::Variable::new(1 + rng.below(vars) as u32);
+                        lit::Literal::new(v, rng.below(2) == 1)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn small() {
+        let clauses = [
+            clause(&[1, 2]),
+            clause(&[3]),
+            clause(&[-1]),
+            clause(&[-2]),
+            clause(&[-3, 4]),
+        ];
+        assert_eq!(mus(4, &clauses), Some(vec![0, 2, 3]));
+
+        assert_eq!(mus(2, &clauses[..2]), None);
+        assert_eq!(mus(0, &[clause(&[1]), clause(&[])]), Some(vec![1]));
+    }
+
+    #[test]
+    fn groups() {
+        let hard = [clause(&[1, 2])];
+        let groups = [
+            vec![clause(&[-1]), clause(&[3])],
+            vec![clause(&[-2])],
+            vec![clause(&[4])],
+            vec![clause(&[-4, 5])],
+        ];
+        assert_eq!(group_mus(5, &hard, &groups), Some(vec![0, 1]));
+        assert_eq!(group_mus(5, &hard, &groups[1..]), None);
+
+        // The hard clauses are unsatisfiable without any groups.
+        let hard = [clause(&[1]), clause(&[-1])];
+        assert_eq!(group_mus(1, &hard, &groups), Some(vec![]));
+    }
+
+    #[test]
+    fn random() {
+        let mut rng = Random::new(17);
+        for _ in 0..50 {
+            let clauses = random_clauses(&mut rng, 6, 30);
+            let groups: Vec<Vec<cls::Clause>> = clauses.iter().map(|c| vec![c.clone()]).collect();
+            match mus(6, &clauses) {
+                Some(indexes) => assert!(is_mus(&[], &groups, &indexes)),
+                None => assert!(satisfiable(&clauses)),
+            }
+        }
+    }
+
+    #[test]
+    fn random_groups() {
+        let mut rng = Random::new(23);
+        for _ in 0..50 {
+            let hard = random_clauses(&mut rng, 6, 3);
+            let groups: Vec<Vec<cls::Clause>> = (0..12)
+                .map(|_| {
+                    let n = 1 + rng.below(3);
+                    random_clauses(&mut rng, 6, n)
+                })
+                .collect();
+            match group_mus(6, &hard, &groups) {
+                Some(indexes) => assert!(is_mus(&hard, &groups, &indexes)),
+                None => {
+                    let all: Vec<cls::Clause> = hard
+                        .iter()
+                        .chain(groups.iter().flatten())
+                        .cloned()
+                        .collect();
+                    assert!(satisfiable(&all));
+                }
+            }
+        }
+    }
+}
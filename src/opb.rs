@@ -0,0 +1,254 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use super::literal as l;
+use super::pseudo_boolean as pb;
+
+use std::io::{BufRead, Result, Write};
+
+/// A Pseudo-Boolean problem, as read from an OPB file.
+#[derive(Debug, Clone, Default)]
+pub struct Problem {
+    pub constraints: Vec<pb::PseudoBoolean>,
+    /// The terms of the objective to minimize, if there is one.
+    pub objective: Option<Vec<(i64, l::Literal)>>,
+}
+
+/// An OPB File Parser
+///
+/// The format is (nearly) as simple as DIMACS:
+/// - `* ...` is a comment
+/// - `min: +2 x1 -1 x2 ;` is an (optional) objective to minimize.
+/// - `+1 x1 +2 ~x3 >= 2 ;` is a constraint. `~x3` is `x3` negated. The
+///   relation can also be `=` or `<=`.
+///
+/// Constraints end with `;`, rather than at the end of a line. Non-linear
+/// terms (like `+1 x1 x2`) are not supported.
+pub fn parse<R: BufRead>(buf: R) -> Option<Problem> {
+    let mut problem = Problem::default();
+    let mut found_anything = false;
+    let mut statement: Vec<String> = vec![];
+
+    for res in buf.lines() {
+        let line = res.ok()?;
+
+        if line.starts_with('*') {
+            // Comment line
+            continue;
+        }
+
+        // Make sure `;` is a token on its own, however it was written.
+        for token in line.replace(';', " ; ").split_ascii_whitespace() {
+            if token != ";" {
+                statement.push(token.to_string());
+                continue;
+            }
+
+            if statement.is_empty() {
+                return None;
+            }
+            found_anything = true;
+
+            if statement[0] == "min:" {
+                if problem.objective.is_some() {
+                    return None;
+                }
+                let objective = parse_terms(&statement[1..])?;
+                if !fits(&objective, 0) {
+                    return None;
+                }
+                problem.objective = Some(objective);
+            } else {
+                parse_constraint(&statement, &mut problem.constraints)?;
+            }
+
+            statement.clear();
+        }
+    }
+
+    // An unterminated statement is an error.
+    if !statement.is_empty() || !found_anything {
+        return None;
+    }
+
+    Some(problem)
+}
+
+fn parse_constraint(statement: &[String], into: &mut Vec<pb::PseudoBoolean>) -> Option<()> {
+    if statement.len() < 2 {
+        return None;
+    }
+
+    let (relation, bound) = (
+        &statement[statement.len() - 2],
+        &statement[statement.len() - 1],
+    );
+    let terms = parse_terms(&statement[..statement.len() - 2])?;
+    let bound: i64 = bound.parse().ok()?;
+    if !fits(&terms, bound) {
+        return None;
+    }
+
+    match relation.as_str() {
+        ">=" => into.push(pb::PseudoBoolean::at_least(terms, bound)),
+        "<=" => into.push(pb::PseudoBoolean::at_most(terms, bound)),
+        "=" => {
+            into.push(pb::PseudoBoolean::at_least(terms.clone(), bound));
+            into.push(pb::PseudoBoolean::at_most(terms, bound));
+        }
+        _ => return None,
+    }
+
+    Some(())
+}
+
+// Terms come in pairs of coefficient and literal.
+fn parse_terms(tokens: &[String]) -> Option<Vec<(i64, l::Literal)>> {
+    if !tokens.len().is_multiple_of(2) {
+        return None;
+    }
+
+    tokens
+        .chunks(2)
+        .map(|pair| {
+            let coeff: i64 = pair[0].parse().ok()?;
+            Some((coeff, parse_literal(&pair[1])?))
+        })
+        .collect()
+}
+
+// Whether the sizes of every coefficient and the bound add up to something
+// an `i64` can hold, so no sum of them, like a cost, can overflow.
+fn fits(terms: &[(i64, l::Literal)], bound: i64) -> bool {
+    terms
+        .iter()
+        .try_fold(bound.unsigned_abs(), |sum, (c, _)| {
+            sum.checked_add(c.unsigned_abs())
+        })
+        .is_some_and(|sum| sum <= i64::MAX as u64)
+}
+
+fn parse_literal(token: &str) -> Option<l::Literal> {
+    let (polarity, name) = match token.strip_prefix('~') {
+        Some(name) => (false, name),
+        None => (true, token),
+    };
+
+    let index: u32 = name.strip_prefix('x')?.parse().ok()?;
+    if index == 0 {
+        return None;
+    }
+
+    Some(l::Literal::new(l::Variable::new(index), polarity))
+}
+
+/// A Pseudo-Boolean Solution Printer
+///
+/// This is like the DIMACS one, but values are written `x1 -x2`, and there's
+/// no terminating 0.
+pub fn print<W: Write>(buf: &mut W, soln: Option<Vec<l::Literal>>) -> Result<()> {
+    match soln {
+        None => writeln!(buf, "s UNSATISFIABLE"),
+        Some(soln) => {
+            writeln!(buf, "s SATISFIABLE")?;
+            print_values(buf, &soln)
+        }
+    }
+}
+
+/// Print an intermediate solution's cost, while optimizing.
+pub fn print_cost<W: Write>(buf: &mut W, cost: i64) -> Result<()> {
+    writeln!(buf, "o {}", cost)
+}
+
+/// Print the result of optimizing, where any solution is an optimal one.
+pub fn print_optimum<W: Write>(buf: &mut W, soln: Option<Vec<l::Literal>>) -> Result<()> {
+    match soln {
+        None => writeln!(buf, "s UNSATISFIABLE"),
+        Some(soln) => {
+            writeln!(buf, "s OPTIMUM FOUND")?;
+            print_values(buf, &soln)
+        }
+    }
+}
+
+fn print_values<W: Write>(buf: &mut W, soln: &[l::Literal]) -> Result<()> {
+    write!(buf, "v")?;
+    for l in soln {
+        if l.polarity() {
+            write!(buf, " x{}", l.variable().index())?;
+        } else {
+            write!(buf, " -x{}", l.variable().index())?;
+        }
+    }
+    writeln!(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lit(l: i32) -> l::Literal {
+        l::Literal::from_dimacs(l).unwrap()
+    }
+
+    #[test]
+    fn constraints() {
+        let input = "* #variable= 3 #constraint= 2\n\
+                     +1 x1 +2 ~x2 >= 2 ;\n\
+                     +1 x1 +1 x2\n+1 x3 = 1;\n";
+        let problem = parse(input.as_bytes()).unwrap();
+
+        assert!(problem.objective.is_none());
+        assert_eq!(problem.constraints.len(), 3);
+        assert_eq!(
+            problem.constraints[0],
+            pb::PseudoBoolean::at_least(vec![(1, lit(1)), (2, lit(-2))], 2)
+        );
+    }
+
+    #[test]
+    fn objective() {
+        let input = "min: +3 x1 -1 ~x2 ;\n+1 x1 +1 x2 >= 1 ;\n";
+        let problem = parse(input.as_bytes()).unwrap();
+
+        assert_eq!(problem.objective, Some(vec![(3, lit(1)), (-1, lit(-2))]));
+        assert_eq!(problem.constraints.len(), 1);
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(parse("".as_bytes()).is_none());
+        assert!(parse("+1 x1 >= 1".as_bytes()).is_none());
+        assert!(parse("+1 x1 x2 >= 1 ;".as_bytes()).is_none());
+        assert!(parse("+1 y1 >= 1 ;".as_bytes()).is_none());
+        assert!(parse("+1 x1 > 1 ;".as_bytes()).is_none());
+
+        // Coefficients which could overflow when added up.
+        let max = i64::MAX;
+        assert!(parse(format!("+{max} x1 +1 x2 >= 0 ;").as_bytes()).is_none());
+        assert!(parse(format!("+{max} x1 >= -1 ;").as_bytes()).is_none());
+        assert!(parse(format!("min: -{max} x1 -1 x2 ;").as_bytes()).is_none());
+        assert!(parse(format!("+{max} x1 >= 0 ;").as_bytes()).is_some());
+    }
+
+    #[test]
+    fn print_solution() {
+        let mut out = vec![];
+        print(&mut out, Some(vec![lit(1), lit(-2)])).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "s SATISFIABLE\nv x1 -x2\n");
+    }
+
+    #[test]
+    fn print_optimization() {
+        let mut out = vec![];
+        print_cost(&mut out, 4).unwrap();
+        print_optimum(&mut out, Some(vec![lit(-1), lit(2)])).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "o 4\ns OPTIMUM FOUND\nv -x1 x2\n"
+        );
+    }
+}
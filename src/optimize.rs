@@ -0,0 +1,176 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use super::cdcl;
+use super::literal as lit;
+use super::pseudo_boolean as pb;
+
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+
+/// The value of `objective` under `soln`.
+pub fn cost(objective: &[(i64, lit::Literal)], soln: &[lit::Literal]) -> i64 {
+    objective
+        .iter()
+        .filter(|(_, l)| soln.contains(l))
+        .map(|(c, _)| c)
+        .sum()
+}
+
+/// Find a solution to the problem in `solver` which minimizes `objective`,
+/// using linear SAT-UNSAT search.
+///
+/// Every time we find a solution, we add a constraint that the objective must
+/// be strictly smaller than its cost, and solve again. When that is
+/// unsatisfiable, the last solution was optimal. `improved` is called with
+/// each solution's cost as it is found.
+///
+/// Returns:
+/// - None if the problem is `unsat`
+/// - Some((cost, Solution)) for an optimal solution otherwise
+pub fn minimize<F: FnMut(i64)>(
+    solver: &mut cdcl::Solver,
+    objective: &[(i64, lit::Literal)],
+    mut improved: F,
+) -> Option<(i64, Vec<lit::Literal>)> {
+    let mut best = None;
+
+    // The objective might mention variables no constraint does, which still
+    // need values.
+    for (_, l) in objective {
+        solver.add_variable(l.variable());
+    }
+
+    let bound = Arc::new(AtomicI64::new(i64::MAX));
+    solver.add_propagator(Box::new(ObjectiveBound::new(objective, bound.clone())));
+
+    while solver.solve() == cdcl::Answer::Satisfiable {
+        let soln = solver.model().to_vec();
+        let value = cost(objective, &soln);
+        improved(value);
+        best = Some((value, soln));
+
+        match value.checked_sub(1) {
+            Some(value) => bound.store(value, Ordering::Relaxed),
+            // Nothing can cost less than this.
+            None => break,
+        }
+    }
+
+    best
+}
+
+/// Keeps the objective at most a bound, which can be lowered while the solver
+/// has it.
+///
+/// Lowering the bound can make a coefficient that used to be capped count
+/// for more, so the constraint is built again from the objective, rather
+/// than changed.
+struct ObjectiveBound {
+    objective: Vec<(i64, lit::Literal)>,
+    bound: Arc<AtomicI64>,
+    // The bound `propagator` was built with.
+    current: i64,
+    propagator: pb::PseudoBooleanPropagator,
+}
+
+impl ObjectiveBound {
+    fn new(objective: &[(i64, lit::Literal)], bound: Arc<AtomicI64>) -> ObjectiveBound {
+        let current = bound.load(Ordering::Relaxed);
+        ObjectiveBound {
+            objective: objective.to_vec(),
+            bound,
+            current,
+            propagator: ObjectiveBound::build(objective, current),
+        }
+    }
+
+    fn build(objective: &[(i64, lit::Literal)], bound: i64) -> pb::PseudoBooleanPropagator {
+        let constraint = pb::PseudoBoolean::at_most(objective.to_vec(), bound);
+        pb::PseudoBooleanPropagator::new(&[constraint])
+    }
+}
+
+impl cdcl::Propagator for ObjectiveBound {
+    fn variables(&self) -> Vec<lit::Variable> {
+        self.propagator.variables()
+    }
+
+    fn propagate(&mut self, trail: &cdcl::Trail) -> Result<Vec<cdcl::Reason>, cdcl::Reason> {
+        let bound = self.bound.load(Ordering::Relaxed);
+        if bound != self.current {
+            // A new propagator catches up with the whole trail.
+            self.current = bound;
+            self.propagator = ObjectiveBound::build(&self.objective, bound);
+        }
+        self.propagator.propagate(trail)
+    }
+
+    fn backtrack(&mut self, trail: &cdcl::Trail, len: usize) {
+        self.propagator.backtrack(trail, len);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lit(l: i32) -> lit::Literal {
+        lit::Literal::from_dimacs(l).unwrap()
+    }
+
+    fn terms(ts: &[(i64, i32)]) -> Vec<(i64, lit::Literal)> {
+        ts.iter().map(|(c, l)| (*c, lit(*l))).collect()
+    }
+
+    #[test]
+    fn unsatisfiable() {
+        let mut solver = cdcl::Solver::new();
+        solver.add_clause(vec![lit(1)]);
+        solver.add_clause(vec![lit(-1)]);
+
+        assert!(minimize(&mut solver, &terms(&[(1, 1)]), |_| ()).is_none());
+    }
+
+    #[test]
+    fn cheapest_cover() {
+        // Cover {1, 2, 3} with sets a = {1, 2} (cost 3), b = {2, 3} (cost 3),
+        // c = {1}, d = {3} and e = {2} (cost 1 each). c, d and e together are
+        // best.
+        let mut solver = cdcl::Solver::new();
+        solver.add_clause(vec![lit(1), lit(3)]);
+        solver.add_clause(vec![lit(1), lit(2), lit(5)]);
+        solver.add_clause(vec![lit(2), lit(4)]);
+        let objective = terms(&[(3, 1), (3, 2), (1, 3), (1, 4), (1, 5)]);
+
+        let mut costs = vec![];
+        let (value, soln) = minimize(&mut solver, &objective, |c| costs.push(c)).unwrap();
+
+        assert_eq!(value, 3);
+        assert!(soln.contains(&lit(3)) && soln.contains(&lit(4)) && soln.contains(&lit(5)));
+        assert_eq!(costs.last(), Some(&3));
+        assert!(costs.windows(2).all(|w| w[0] > w[1]));
+    }
+
+    #[test]
+    fn negative_coefficients() {
+        // Maximize x1 + x2 subject to at most one of them.
+        let mut solver = cdcl::Solver::new();
+        solver.add_clause(vec![lit(-1), lit(-2)]);
+
+        let (value, _) = minimize(&mut solver, &terms(&[(-1, 1), (-1, 2)]), |_| ()).unwrap();
+        assert_eq!(value, -1);
+    }
+
+    #[test]
+    fn cheapest_possible() {
+        // Nothing is cheaper than `i64::MIN`, so there's no bound to add.
+        let mut solver = cdcl::Solver::new();
+        solver.add_clause(vec![lit(1)]);
+
+        let (value, _) = minimize(&mut solver, &terms(&[(i64::MIN, 1)]), |_| ()).unwrap();
+        assert_eq!(value, i64::MIN);
+    }
+}
@@ -0,0 +1,297 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use super::cdcl;
+use super::literal as lit;
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// The answer from whichever solver finished first.
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    pub answer: cdcl::Answer,
+    /// The winner's model, if the answer is `Satisfiable`.
+    pub model: Vec<lit::Literal>,
+    /// Which configuration won, or `None` if none of them finished.
+    pub winner: Option<usize>,
+}
+
+/// Which learnt clauses the solvers share with each other. A clause is shared
+/// if it is short enough, or if its LBD is low enough.
+#[derive(Debug, Clone)]
+pub struct Sharing {
+    /// Share clauses with at most this many literals.
+    pub max_len: usize,
+    /// Share clauses whose literals were in at most this many decision levels
+    /// when they were learnt.
+    pub max_lbd: usize,
+    /// The most clauses a solver takes from the others at each restart.
+    pub max_import: usize,
+    /// The most clauses we keep for solvers to take. Older ones are forgotten,
+    /// even if some solvers haven't taken them yet.
+    pub capacity: usize,
+}
+
+impl Default for Sharing {
+    fn default() -> Sharing {
+        Sharing {
+            max_len: 2,
+            max_lbd: 2,
+            max_import: 1000,
+            capacity: 100_000,
+        }
+    }
+}
+
+// Every clause shared so far, and which solver it came from.
+#[derive(Debug, Default)]
+struct Pool {
+    clauses: VecDeque<(usize, Vec<lit::Literal>)>,
+    // How many clauses have been dropped from the front of `clauses`.
+    forgotten: usize,
+}
+
+// One solver's view of the pool.
+struct Worker {
+    id: usize,
+    pool: Arc<Mutex<Pool>>,
+    sharing: Sharing,
+    // The index of the next clause to import, counting forgotten clauses.
+    next: usize,
+}
+
+impl cdcl::Exchange for Worker {
+    fn export(&mut self, lits: &[lit::Literal], lbd: usize) {
+        if lits.len() > self.sharing.max_len && lbd > self.sharing.max_lbd {
+            return;
+        }
+
+        let mut pool = self.pool.lock().unwrap();
+        pool.clauses.push_back((self.id, lits.to_vec()));
+        if pool.clauses.len() > self.sharing.capacity {
+            pool.clauses.pop_front();
+            pool.forgotten += 1;
+        }
+    }
+
+    fn import(&mut self) -> Vec<Vec<lit::Literal>> {
+        let pool = self.pool.lock().unwrap();
+        let start = self.next.max(pool.forgotten) - pool.forgotten;
+
+        let mut imported = vec![];
+        let mut taken = 0;
+        for (source, lits) in pool.clauses.iter().skip(start) {
+            if imported.len() == self.sharing.max_import {
+                break;
+            }
+            taken += 1;
+            if *source != self.id {
+                imported.push(lits.clone());
+            }
+        }
+
+        self.next = pool.forgotten + start + taken;
+        imported
+    }
+}
+
+/// `n` configurations which differ as much as we can manage, in seeds,
+/// restart policies, variable activity decay, random decisions and initial
+/// phases. The first is always the default.
+pub fn diversify(n: usize) -> Vec<cdcl::Config> {
+    (0..n).map(configuration).collect()
+}
+
+/// The `i`th configuration from `diversify`.
+pub fn configuration(i: usize) -> cdcl::Config {
+    if i == 0 {
+        return cdcl::Config::default();
+    }
+
+    let restarts = if i % 2 == 1 {
+        cdcl::Restarts::Geometric {
+            first: 100,
+            factor: 1.5,
+        }
+    } else {
+        cdcl::Restarts::Luby {
+            unit: [100, 512, 50][i / 2 % 3],
+        }
+    };
+
+    cdcl::Config {
+        seed: i as u64,
+        restarts,
+        var_decay: [0.95, 0.85, 0.99, 0.9][i % 4],
+        random_decisions: if i.is_multiple_of(3) { 0.01 } else { 0.0 },
+        initial_phase: i % 4 >= 2,
+    }
+}
+
+/// Run a solver for each of `configs` in its own thread, and return the first
+/// answer. `setup` is called once per solver, in its thread, to give it the
+/// problem. If `sharing` is given, the solvers share the clauses they learn.
+///
+/// As soon as one solver has an answer, the others are interrupted, and we
+/// wait for them to notice before returning.
+pub fn solve<F>(configs: &[cdcl::Config], sharing: Option<&Sharing>, setup: F) -> Outcome
+where
+    F: Fn(&mut cdcl::Solver) + Sync,
+{
+    let stop = Arc::new(AtomicBool::new(false));
+    let pool = Arc::new(Mutex::new(Pool::default()));
+    let outcome = Mutex::new(Outcome {
+        answer: cdcl::Answer::Unknown,
+        model: vec![],
+        winner: None,
+    });
+
+    thread::scope(|scope| {
+        for (idx, config) in configs.iter().enumerate() {
+            let (stop, pool, outcome, setup) = (&stop, &pool, &outcome, &setup);
+            scope.spawn(move || {
+                let mut solver = cdcl::Solver::with_config(config.clone());
+                solver.set_interrupt(stop.clone());
+                setup(&mut solver);
+                if let Some(sharing) = sharing {
+                    solver.set_exchange(Box::new(Worker {
+                        id: idx,
+                        pool: pool.clone(),
+                        sharing: sharing.clone(),
+                        next: 0,
+                    }));
+                }
+
+                let answer = solver.solve();
+                if answer == cdcl::Answer::Unknown {
+                    return;
+                }
+
+                let mut outcome = outcome.lock().unwrap();
+                if outcome.winner.is_none() {
+                    *outcome = Outcome {
+                        answer,
+                        model: solver.model().to_vec(),
+                        winner: Some(idx),
+                    };
+                    stop.store(true, Ordering::Relaxed);
+                }
+            });
+        }
+    });
+
+    outcome.into_inner().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdcl::tests::pigeons;
+    use crate::clause as cls;
+
+    fn lit(l: i32) -> lit::Literal {
+        lit::Literal::from_dimacs(l).unwrap()
+    }
+
+    fn setup(clauses: &[cls::Clause]) -> impl Fn(&mut cdcl::Solver) + Sync + '_ {
+        move |solver| {
+            for c in clauses {
+                solver.add_clause(c.iter().copied());
+            }
+        }
+    }
+
+    #[test]
+    fn diverse() {
+        let configs = diversify(8);
+        assert_eq!(configs.len(), 8);
+        assert_eq!(configs[0].seed, 0);
+        for (i, a) in configs.iter().enumerate() {
+            for b in &configs[i + 1..] {
+                assert_ne!(a.seed, b.seed);
+            }
+        }
+    }
+
+    #[test]
+    fn unsatisfiable() {
+        let outcome = solve(&diversify(4), None, setup(&pigeons(6)));
+
+        assert_eq!(outcome.answer, cdcl::Answer::Unsatisfiable);
+        assert!(outcome.winner.is_some());
+        assert!(outcome.model.is_empty());
+    }
+
+    #[test]
+    fn satisfiable() {
+        // The last pigeon doesn't have to go anywhere, so the rest fit.
+        let mut clauses = pigeons(6);
+        clauses.remove(6);
+        let outcome = solve(&diversify(4), None, setup(&clauses));
+
+        assert_eq!(outcome.answer, cdcl::Answer::Satisfiable);
+        for c in &clauses {
+            assert!(c.iter().any(|l| outcome.model.contains(l)));
+        }
+    }
+
+    #[test]
+    fn sharing() {
+        let outcome = solve(&diversify(3), Some(&Sharing::default()), setup(&pigeons(6)));
+        assert_eq!(outcome.answer, cdcl::Answer::Unsatisfiable);
+
+        let mut clauses = pigeons(6);
+        clauses.remove(6);
+        let outcome = solve(&diversify(3), Some(&Sharing::default()), setup(&clauses));
+        assert_eq!(outcome.answer, cdcl::Answer::Satisfiable);
+    }
+
+    #[test]
+    fn pool_limits() {
+        use cdcl::Exchange;
+
+        let pool = Arc::new(Mutex::new(Pool::default()));
+        let sharing = Sharing {
+            max_len: 2,
+            max_lbd: 1,
+            max_import: 2,
+            capacity: 3,
+        };
+        let worker = |id| Worker {
+            id,
+            pool: pool.clone(),
+            sharing: sharing.clone(),
+            next: 0,
+        };
+        let (mut a, mut b) = (worker(0), worker(1));
+        let c = |ls: &[i32]| ls.iter().copied().map(lit).collect::<Vec<_>>();
+
+        // Too long, and LBD too high.
+        a.export(&c(&[1, 2, 3]), 2);
+        assert!(b.import().is_empty());
+
+        a.export(&c(&[1, 2, 3]), 1);
+        a.export(&c(&[4, 5]), 2);
+        b.export(&c(&[6]), 1);
+        a.export(&c(&[7]), 1);
+
+        // Nothing from itself, at most two at a time, and the oldest clause
+        // has been forgotten.
+        assert_eq!(a.import(), vec![c(&[6])]);
+        assert!(a.import().is_empty());
+        assert_eq!(b.import(), vec![c(&[4, 5]), c(&[7])]);
+        assert!(b.import().is_empty());
+    }
+
+    #[test]
+    fn no_solvers() {
+        let outcome = solve(&[], None, |_| ());
+        assert_eq!(outcome.answer, cdcl::Answer::Unknown);
+        assert!(outcome.winner.is_none());
+    }
+}
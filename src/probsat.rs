@@ -0,0 +1,156 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use super::clause as cls;
+use super::local_search::LocalSearch;
+use super::random::Random;
+use super::walksat::{State, Walk};
+
+type Problem<'a> = &'a [cls::Clause];
+
+/// How likely probSAT is to flip a variable, given how many clauses flipping
+/// it would break.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Distribution {
+    /// `(eps + breaks) ^ -cb`
+    Polynomial { cb: f64, eps: f64 },
+    /// `cb ^ -breaks`
+    Exponential { cb: f64 },
+}
+
+impl Distribution {
+    /// The distribution that works best on uniform random k-SAT, from the
+    /// probSAT paper.
+    pub fn for_clause_length(k: usize) -> Distribution {
+        match k {
+            0..=3 => Distribution::Polynomial { cb: 2.38, eps: 1.0 },
+            4 => Distribution::Exponential { cb: 3.0 },
+            5 => Distribution::Exponential { cb: 3.7 },
+            6 => Distribution::Exponential { cb: 5.1 },
+            _ => Distribution::Exponential { cb: 5.4 },
+        }
+    }
+
+    fn weight(&self, breaks: u64) -> f64 {
+        match *self {
+            Distribution::Polynomial { cb, eps } => (eps + breaks as f64).powf(-cb),
+            Distribution::Exponential { cb } => cb.powf(-(breaks as f64)),
+        }
+    }
+}
+
+/// probSAT, from "Choosing Probability Distributions for Stochastic Local
+/// Search and the Role of Make versus Break" (Balint, Schöning, 2012).
+///
+/// Like WalkSAT, we repeatedly pick an unsatisfied clause and flip one of its
+/// variables, but there's no greedy step: every variable in the clause might
+/// be picked, with a probability that falls as the number of clauses it would
+/// break grows.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub distribution: Distribution,
+    /// How many variables to flip before giving up.
+    pub max_flips: u64,
+    pub seed: u64,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            distribution: Distribution::for_clause_length(3),
+            max_flips: 1_000_000,
+            seed: 0,
+        }
+    }
+}
+
+impl LocalSearch for Config {
+    fn search(&self, clauses: Problem) -> Walk {
+        let mut rng = Random::new(self.seed);
+        let mut state = State::new(clauses, &mut rng);
+        let mut weights = vec![];
+
+        let mut best = state.assignment();
+        let mut best_unsatisfied = state.unsatisfied.len();
+        let mut flips = 0;
+
+        while !state.unsatisfied.is_empty() && flips < self.max_flips {
+            let clause = state.unsatisfied[rng.below(state.unsatisfied.len())];
+            let vars = state.clause_vars(clause);
+
+            if vars.is_empty() {
+                // The empty clause, which no amount of flipping will fix.
+                break;
+            }
+
+            weights.clear();
+            weights.extend(
+                vars.iter()
+                    .map(|v| self.distribution.weight(state.breaks[*v])),
+            );
+
+            let mut pick = rng.fraction() * weights.iter().sum::<f64>();
+            let mut var = vars[vars.len() - 1];
+            for (v, w) in vars.iter().zip(&weights) {
+                if pick < *w {
+                    var = *v;
+                    break;
+                }
+                pick -= w;
+            }
+
+            state.flip(var);
+            flips += 1;
+
+            if state.unsatisfied.len() < best_unsatisfied {
+                best_unsatisfied = state.unsatisfied.len();
+                best = state.assignment();
+            }
+        }
+
+        Walk {
+            assignment: best,
+            unsatisfied: best_unsatisfied,
+            flips,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::local_search::tests::{planted, satisfies};
+
+    #[test]
+    fn polynomial() {
+        let clauses = planted(200, 800, 3, 4);
+        let walk = Config::default().search(&clauses);
+
+        assert!(walk.is_solution());
+        assert!(satisfies(&walk.assignment, &clauses));
+    }
+
+    #[test]
+    fn exponential() {
+        let clauses = planted(100, 1500, 5, 5);
+        let config = Config {
+            distribution: Distribution::for_clause_length(5),
+            ..Config::default()
+        };
+        let walk = config.search(&clauses);
+
+        assert!(walk.is_solution());
+        assert!(satisfies(&walk.assignment, &clauses));
+    }
+
+    #[test]
+    fn fewer_breaks_more_likely() {
+        for k in 3..8 {
+            let d = Distribution::for_clause_length(k);
+            assert!(d.weight(0) > d.weight(1));
+            assert!(d.weight(1) > d.weight(5));
+        }
+    }
+}
@@ -0,0 +1,327 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use super::assumptions as a;
+use super::cdcl;
+use super::literal as lit;
+
+use std::collections::BTreeMap;
+
+/// A linear pseudo-Boolean constraint, i.e. `a1*l1 + a2*l2 + ... >= bound`,
+/// where a true literal counts as 1 and a false one as 0.
+///
+/// Constraints are kept in a normal form where every coefficient is positive
+/// and no bigger than the bound. Negative coefficients are removed by negating
+/// their literal, as `-a*l` is the same as `a*~l - a`.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct PseudoBoolean {
+    terms: Vec<(u64, lit::Literal)>,
+    bound: u64,
+}
+
+impl PseudoBoolean {
+    pub fn at_least(terms: Vec<(i64, lit::Literal)>, bound: i64) -> PseudoBoolean {
+        PseudoBoolean::normalize(
+            terms.into_iter().map(|(c, l)| (c as i128, l)),
+            bound as i128,
+        )
+    }
+
+    pub fn at_most(terms: Vec<(i64, lit::Literal)>, bound: i64) -> PseudoBoolean {
+        PseudoBoolean::normalize(
+            terms.into_iter().map(|(c, l)| (-(c as i128), l)),
+            -(bound as i128),
+        )
+    }
+
+    // Everything is done in `i128`, where no `i64` coefficient or bound can
+    // overflow when negated or summed.
+    fn normalize(terms: impl Iterator<Item = (i128, lit::Literal)>, bound: i128) -> PseudoBoolean {
+        let mut bound = bound;
+        let mut normal = vec![];
+
+        for (coeff, l) in terms {
+            if coeff < 0 {
+                bound -= coeff;
+                normal.push((coeff.unsigned_abs() as u64, l.negate()));
+            } else if coeff > 0 {
+                normal.push((coeff as u64, l));
+            }
+        }
+
+        // A bound below zero is trivially satisfied, same as a bound of zero.
+        // One too big for a `u64` saturates, which is only wrong if the
+        // coefficients add up to more than that.
+        let bound = bound.clamp(0, u64::MAX as i128) as u64;
+
+        // A coefficient bigger than the bound can't do any more than satisfy
+        // the constraint on its own.
+        for term in normal.iter_mut() {
+            term.0 = term.0.min(bound);
+        }
+
+        PseudoBoolean {
+            terms: normal,
+            bound,
+        }
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, (u64, lit::Literal)> {
+        self.terms.iter()
+    }
+
+    pub fn bound(&self) -> u64 {
+        self.bound
+    }
+
+    /// Is this constraint satisfied by `soln`?
+    pub fn is_satisfied(&self, soln: &[lit::Literal]) -> bool {
+        let sum: i128 = self
+            .terms
+            .iter()
+            .filter(|(_, l)| soln.contains(l))
+            .map(|(c, _)| *c as i128)
+            .sum();
+        sum >= self.bound as i128
+    }
+
+    // Big enough for any number of `u64` coefficients we could have memory
+    // for.
+    fn total(&self) -> i128 {
+        self.terms.iter().map(|(c, _)| *c as i128).sum()
+    }
+}
+
+/// Propagates pseudo-Boolean constraints by keeping track of each one's
+/// slack: how far the sum of the coefficients of the literals which aren't
+/// false is above the bound.
+///
+/// - If the slack goes below zero, the false literals are a conflict.
+/// - Any unassigned literal with a coefficient bigger than the slack has to be
+///   true, because the constraint can't be satisfied without it.
+///
+/// Explanations only use as many of the false literals as they need, biggest
+/// coefficients first, which keeps learnt clauses short.
+pub struct PseudoBooleanPropagator {
+    constraints: Vec<PseudoBoolean>,
+    // For each literal, the constraints it appears in, and its coefficient
+    // there.
+    occurrences: BTreeMap<lit::Literal, Vec<(usize, u64)>>,
+    slacks: Vec<i128>,
+    largest: Vec<u64>,
+    // How much of the trail has been counted.
+    processed: usize,
+    // Constraints which need looking at, even if nothing has changed.
+    pending: Vec<usize>,
+}
+
+impl PseudoBooleanPropagator {
+    pub fn new(constraints: &[PseudoBoolean]) -> PseudoBooleanPropagator {
+        let mut occurrences: BTreeMap<lit::Literal, Vec<(usize, u64)>> = BTreeMap::new();
+        for (idx, c) in constraints.iter().enumerate() {
+            for (coeff, l) in c.iter() {
+                occurrences.entry(*l).or_default().push((idx, *coeff));
+            }
+        }
+
+        PseudoBooleanPropagator {
+            constraints: constraints.to_vec(),
+            occurrences,
+            slacks: constraints
+                .iter()
+                .map(|c| c.total() - c.bound as i128)
+                .collect(),
+            largest: constraints
+                .iter()
+                .map(|c| c.iter().map(|(c, _)| *c).max().unwrap_or(0))
+                .collect(),
+            processed: 0,
+            pending: (0..constraints.len()).collect(),
+        }
+    }
+
+    fn check(
+        &self,
+        idx: usize,
+        trail: &cdcl::Trail,
+        implied: &mut Vec<cdcl::Reason>,
+    ) -> Result<(), cdcl::Reason> {
+        let slack = self.slacks[idx];
+        if slack >= self.largest[idx] as i128 {
+            // Nothing is forced yet.
+            return Ok(());
+        }
+
+        let constraint = &self.constraints[idx];
+        let mut falsified: Vec<(u64, lit::Literal)> = constraint
+            .iter()
+            .copied()
+            .filter(|(_, l)| trail.get_lit(*l) == a::Assumption::Assume(false))
+            .collect();
+        falsified.sort_by_key(|(c, _)| std::cmp::Reverse(*c));
+
+        if slack < 0 {
+            return Err(explain(
+                &falsified,
+                constraint.total() - constraint.bound as i128,
+            ));
+        }
+
+        for (coeff, l) in constraint.iter().copied() {
+            if coeff as i128 > slack && trail.get_lit(l) == a::Assumption::Unknown {
+                // Without `l`, the false literals have to take away more than
+                // the slack there would be.
+                let needed = constraint.total() - coeff as i128 - constraint.bound as i128;
+                let mut reason = vec![l];
+                reason.extend(explain(&falsified, needed));
+                implied.push(reason);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// The fewest false literals (from `falsified`, sorted biggest coefficient
+// first) whose coefficients add up to more than `slack`.
+fn explain(falsified: &[(u64, lit::Literal)], slack: i128) -> Vec<lit::Literal> {
+    let mut sum = 0;
+    let mut reason = vec![];
+    for (coeff, l) in falsified {
+        if sum > slack {
+            break;
+        }
+        sum += *coeff as i128;
+        reason.push(*l);
+    }
+    reason
+}
+
+impl cdcl::Propagator for PseudoBooleanPropagator {
+    fn variables(&self) -> Vec<lit::Variable> {
+        self.occurrences.keys().map(|l| l.variable()).collect()
+    }
+
+    fn propagate(&mut self, trail: &cdcl::Trail) -> Result<Vec<cdcl::Reason>, cdcl::Reason> {
+        let mut touched = std::mem::take(&mut self.pending);
+
+        for l in &trail.literals()[self.processed..] {
+            if let Some(constraints) = self.occurrences.get(&l.negate()) {
+                for (idx, coeff) in constraints {
+                    self.slacks[*idx] -= *coeff as i128;
+                    touched.push(*idx);
+                }
+            }
+        }
+        self.processed = trail.literals().len();
+
+        touched.sort_unstable();
+        touched.dedup();
+
+        let mut implied = vec![];
+        for idx in touched {
+            self.check(idx, trail, &mut implied)?;
+        }
+
+        Ok(implied)
+    }
+
+    fn backtrack(&mut self, trail: &cdcl::Trail, len: usize) {
+        if len >= self.processed {
+            return;
+        }
+
+        for l in &trail.literals()[len..self.processed] {
+            if let Some(constraints) = self.occurrences.get(&l.negate()) {
+                for (idx, coeff) in constraints {
+                    self.slacks[*idx] += *coeff as i128;
+                }
+            }
+        }
+        self.processed = len;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lit(l: i32) -> lit::Literal {
+        lit::Literal::from_dimacs(l).unwrap()
+    }
+
+    fn terms(ts: &[(i64, i32)]) -> Vec<(i64, lit::Literal)> {
+        ts.iter().map(|(c, l)| (*c, lit(*l))).collect()
+    }
+
+    fn solve(constraints: &[PseudoBoolean], clauses: &[&[i32]]) -> Option<Vec<lit::Literal>> {
+        let mut solver = cdcl::Solver::new();
+        for c in clauses {
+            solver.add_clause(c.iter().copied().map(lit));
+        }
+        solver.add_propagator(Box::new(PseudoBooleanPropagator::new(constraints)));
+
+        match solver.solve() {
+            cdcl::Answer::Satisfiable => Some(solver.model().to_vec()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn normalize() {
+        // 3x1 - 2x2 >= 1 is 3x1 + 2~x2 >= 3
+        let pb = PseudoBoolean::at_least(terms(&[(3, 1), (-2, 2)]), 1);
+        assert_eq!(pb.bound(), 3);
+        assert_eq!(
+            pb.iter().copied().collect::<Vec<_>>(),
+            vec![(3, lit(1)), (2, lit(-2))]
+        );
+    }
+
+    #[test]
+    fn saturate() {
+        let pb = PseudoBoolean::at_least(terms(&[(5, 1), (1, 2)]), 2);
+        assert_eq!(
+            pb.iter().copied().collect::<Vec<_>>(),
+            vec![(2, lit(1)), (1, lit(2))]
+        );
+    }
+
+    #[test]
+    fn propagate_big_coefficient() {
+        // 3x1 + x2 + x3 >= 3: with x2 false, x1 is needed.
+        let pb = PseudoBoolean::at_least(terms(&[(3, 1), (1, 2), (1, 3)]), 3);
+        let soln = solve(&[pb], &[&[-2]]).unwrap();
+        assert!(soln.contains(&lit(1)));
+    }
+
+    #[test]
+    fn knapsack() {
+        // Weights 4, 3, 2 with capacity 5, and value 3, 4, 2 of at least 6.
+        let weight = PseudoBoolean::at_most(terms(&[(4, 1), (3, 2), (2, 3)]), 5);
+        let value = PseudoBoolean::at_least(terms(&[(3, 1), (4, 2), (2, 3)]), 6);
+        let soln = solve(&[weight.clone(), value.clone()], &[]).unwrap();
+        assert!(weight.is_satisfied(&soln));
+        assert!(value.is_satisfied(&soln));
+        assert!(soln.contains(&lit(2)) && soln.contains(&lit(3)));
+
+        let too_much = PseudoBoolean::at_least(terms(&[(3, 1), (4, 2), (2, 3)]), 7);
+        assert!(solve(&[weight, too_much], &[]).is_none());
+    }
+
+    #[test]
+    fn extreme_coefficients() {
+        // -x1 <= i64::MIN is x1 >= 2^63, which no single literal can reach.
+        let pb = PseudoBoolean::at_most(terms(&[(-1, 1)]), i64::MIN);
+        assert_eq!(pb.bound(), 1 << 63);
+        assert!(solve(&[pb], &[]).is_none());
+
+        // These add up to more than a `u64` holds, and are satisfied.
+        let big = [(i64::MAX, 1), (i64::MAX, 2), (i64::MAX, 3)];
+        let pb = PseudoBoolean::at_least(terms(&big), i64::MAX);
+        assert!(pb.is_satisfied(&[lit(1), lit(2), lit(3)]));
+        assert!(solve(&[pb], &[&[-1], &[-2]]).unwrap().contains(&lit(3)));
+    }
+}
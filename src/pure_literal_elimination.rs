@@ -17,6 +17,7 @@ use std::collections::BTreeMap;
 /// for each pure literal in the clauses.
 ///
 /// This cannot generate conflicts, usually.
+#[allow(clippy::single_match)]
 pub fn eliminate(clauses: &[c::Clause], assumptions: &mut a::AssumptionStore) {
     let mut var_info = BTreeMap::new();
 
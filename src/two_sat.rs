@@ -0,0 +1,265 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use super::clause as cls;
+use super::literal as lit;
+
+use std::collections::VecDeque;
+
+type Problem<'a> = &'a [cls::Clause];
+type Solution = Vec<lit::Literal>;
+
+/// A chain of implications `x => ... => ~x => ... => x`, which shows a problem
+/// is unsatisfiable. The first and last literals are the same.
+pub type Contradiction = Vec<lit::Literal>;
+
+/// Can `clauses` be handed to `satisfiable`? That is, does every clause have
+/// at most two literals?
+pub fn is_two_sat(clauses: Problem) -> bool {
+    clauses.iter().all(|c| c.len() <= 2)
+}
+
+/// Check a set of clauses with at most two literals each are satisfiable, in
+/// linear time.
+///
+/// The clause `a OR b` is the same as the implications `~a => b` and
+/// `~b => a`. We build a graph of these implications, and find its strongly
+/// connected components, where every literal in a component has to have the
+/// same value. The problem is unsatisfiable exactly when some `x` and `~x` are
+/// in the same component.
+///
+/// Returns:
+/// - Err(Contradiction) if `unsat`, explaining why
+/// - Ok(Solution) if `sat`
+///
+/// Panics if any clause has more than two literals.
+pub fn satisfiable(clauses: Problem) -> Result<Solution, Contradiction> {
+    let vars = clauses
+        .iter()
+        .flat_map(|c| c.iter())
+        .map(|l| l.variable().index() as usize)
+        .max()
+        .unwrap_or(0);
+
+    let mut graph = vec![vec![]; 2 * (vars + 1)];
+    for c in clauses {
+        let lits: Vec<_> = c.iter().copied().collect();
+        match lits.as_slice() {
+            // The empty clause can never be satisfied, and there's no
+            // implication to blame.
+            [] => return Err(vec![]),
+            // `a` is `a OR a`, i.e. `~a => a`.
+            [a] => graph[a.negate().code()].push(a.code()),
+            [a, b] => {
+                graph[a.negate().code()].push(b.code());
+                graph[b.negate().code()].push(a.code());
+            }
+            _ => panic!("2-SAT clauses can have at most two literals."),
+        }
+    }
+
+    let components = strongly_connected_components(&graph);
+
+    let mut soln = vec![];
+    for v in 1..=vars {
+        let pos = lit::Literal::new(lit::Variable::new(v as u32), true);
+        let (p, n) = (components[pos.code()], components[pos.negate().code()]);
+
+        if p == n {
+            return Err(explain(&graph, &components, pos));
+        }
+
+        // Components are numbered in reverse topological order, so the
+        // literal whose component comes first can't imply its negation.
+        soln.push(if p < n { pos } else { pos.negate() });
+    }
+
+    Ok(soln)
+}
+
+fn decode(code: usize) -> lit::Literal {
+    lit::Literal::new(lit::Variable::new((code / 2) as u32), code % 2 == 1)
+}
+
+/// Tarjan's algorithm, without recursion so deep graphs don't blow the stack.
+/// Returns the component of each node, numbered in the order they are
+/// completed.
+fn strongly_connected_components(graph: &[Vec<usize>]) -> Vec<usize> {
+    const UNVISITED: usize = usize::MAX;
+
+    let mut index = vec![UNVISITED; graph.len()];
+    let mut lowlink = vec![0; graph.len()];
+    let mut on_stack = vec![false; graph.len()];
+    let mut component = vec![UNVISITED; graph.len()];
+    let mut stack = vec![];
+    let mut next_index = 0;
+    let mut next_component = 0;
+
+    for root in 0..graph.len() {
+        if index[root] != UNVISITED {
+            continue;
+        }
+
+        // Each frame is a node, and how many of its edges we've followed.
+        let mut frames = vec![(root, 0)];
+        index[root] = next_index;
+        lowlink[root] = next_index;
+        next_index += 1;
+        stack.push(root);
+        on_stack[root] = true;
+
+        while let Some((node, edge)) = frames.last_mut() {
+            let node = *node;
+            if let Some(&next) = graph[node].get(*edge) {
+                *edge += 1;
+                if index[next] == UNVISITED {
+                    index[next] = next_index;
+                    lowlink[next] = next_index;
+                    next_index += 1;
+                    stack.push(next);
+                    on_stack[next] = true;
+                    frames.push((next, 0));
+                } else if on_stack[next] {
+                    lowlink[node] = lowlink[node].min(index[next]);
+                }
+                continue;
+            }
+
+            // Finished with `node`.
+            frames.pop();
+            if let Some((parent, _)) = frames.last() {
+                lowlink[*parent] = lowlink[*parent].min(lowlink[node]);
+            }
+
+            if lowlink[node] == index[node] {
+                while let Some(member) = stack.pop() {
+                    on_stack[member] = false;
+                    component[member] = next_component;
+                    if member == node {
+                        break;
+                    }
+                }
+                next_component += 1;
+            }
+        }
+    }
+
+    component
+}
+
+// `x` and `~x` are in the same component, so find the paths between them.
+fn explain(graph: &[Vec<usize>], components: &[usize], x: lit::Literal) -> Contradiction {
+    let there = path(graph, components, x.code(), x.negate().code());
+    let back = path(graph, components, x.negate().code(), x.code());
+
+    there
+        .into_iter()
+        .chain(back.into_iter().skip(1))
+        .map(decode)
+        .collect()
+}
+
+// Breadth-first search for the shortest path from `from` to `to`, staying
+// inside their component.
+fn path(graph: &[Vec<usize>], components: &[usize], from: usize, to: usize) -> Vec<usize> {
+    let mut parent = vec![None; graph.len()];
+    let mut queue = VecDeque::new();
+    queue.push_back(from);
+    parent[from] = Some(from);
+
+    while let Some(node) = queue.pop_front() {
+        if node == to {
+            break;
+        }
+        for &next in &graph[node] {
+            if parent[next].is_none() && components[next] == components[from] {
+                parent[next] = Some(node);
+                queue.push_back(next);
+            }
+        }
+    }
+
+    let mut path = vec![to];
+    let mut node = to;
+    while node != from {
+        node = parent[node].expect("Nodes in the same component are connected.");
+        path.push(node);
+    }
+    path.reverse();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lit(l: i32) -> lit::Literal {
+        lit::Literal::from_dimacs(l).unwrap()
+    }
+
+    macro_rules! c {
+        [] => ( cls::Clause::new() );
+        [$($e:expr),+ $(,)?] => ({
+            [$($e),+].iter().copied().map(lit).collect::<cls::Clause>()
+        })
+    }
+
+    fn satisfies(soln: &[lit::Literal], clauses: Problem) -> bool {
+        clauses.iter().all(|c| c.iter().any(|l| soln.contains(l)))
+    }
+
+    // Every step in the cycle has to be an implication from some clause.
+    fn follows(cycle: &[lit::Literal], clauses: Problem) -> bool {
+        cycle.windows(2).all(|step| {
+            clauses.iter().any(|c| {
+                let lits: Vec<_> = c.iter().copied().collect();
+                lits.contains(&step[0].negate()) && lits.contains(&step[1]) || lits == vec![step[1]]
+            })
+        })
+    }
+
+    #[test]
+    fn only_two_sat() {
+        assert!(is_two_sat(&[c![1, 2], c![3]]));
+        assert!(!is_two_sat(&[c![1, 2, 3]]));
+    }
+
+    #[test]
+    fn sat() {
+        let clauses = vec![c![1, 2], c![-1, 3], c![-3, -2], c![4], c![-4, -1]];
+        let soln = satisfiable(&clauses).unwrap();
+
+        assert!(satisfies(&soln, &clauses));
+        assert_eq!(soln.len(), 4);
+    }
+
+    #[test]
+    fn empty_clause() {
+        assert_eq!(satisfiable(&[c![1, 2], c![]]), Err(vec![]));
+    }
+
+    #[test]
+    fn unsat() {
+        let clauses = vec![c![1, 2], c![-1, 2], c![1, -2], c![-1, -2]];
+        let cycle = satisfiable(&clauses).unwrap_err();
+
+        assert_eq!(cycle.first(), cycle.last());
+        assert!(cycle.contains(&cycle[0].negate()));
+        assert!(follows(&cycle, &clauses));
+    }
+
+    #[test]
+    fn unsat_chain() {
+        // 1 => 2 => ... => 50 => ~1, and ~1 => 1.
+        let mut clauses: Vec<_> = (1..50).map(|i| c![-i, i + 1]).collect();
+        clauses.push(c![-50, -1]);
+        clauses.push(c![1]);
+
+        let cycle = satisfiable(&clauses).unwrap_err();
+        assert_eq!(cycle.first(), cycle.last());
+        assert!(follows(&cycle, &clauses));
+        assert!(cycle.len() > 50);
+    }
+}
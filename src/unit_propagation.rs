@@ -11,6 +11,7 @@ use std::collections::VecDeque;
 
 /// Takes a set of clauses, a literal to propagate, and a set of assumptions;
 /// updates assumptions, returns `false` if a conflict was found
+#[allow(clippy::needless_return, clippy::single_match)]
 pub fn propagate(
     clauses: &[c::Clause],
     lit: lit::Literal,
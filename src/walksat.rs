@@ -0,0 +1,417 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use super::clause as cls;
+use super::literal as lit;
+use super::local_search::LocalSearch;
+use super::random::Random;
+
+type Problem<'a> = &'a [cls::Clause];
+type Solution = Vec<lit::Literal>;
+
+/// How WalkSAT should behave.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// The probability of making a random move, rather than a greedy one.
+    pub noise: f64,
+    /// How many variables to flip before giving up.
+    pub max_flips: u64,
+    pub seed: u64,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            noise: 0.5,
+            max_flips: 1_000_000,
+            seed: 0,
+        }
+    }
+}
+
+/// The result of a local search.
+#[derive(Debug, Clone)]
+pub struct Walk {
+    /// The assignment with the fewest unsatisfied clauses that we saw. Every
+    /// variable in the problem has a value.
+    pub assignment: Solution,
+    /// How many clauses `assignment` leaves unsatisfied.
+    pub unsatisfied: usize,
+    pub flips: u64,
+}
+
+impl Walk {
+    /// Did we find a solution? If not, we don't know whether there is one.
+    pub fn is_solution(&self) -> bool {
+        self.unsatisfied == 0
+    }
+}
+
+/// Look for a solution using WalkSAT, from "Noise Strategies for Improving
+/// Local Search" (Selman, Kautz, Cohen, 1994).
+///
+/// We start from a random assignment, and repeatedly pick an unsatisfied
+/// clause, and flip one of its variables:
+/// - If flipping a variable breaks no other clauses, we flip that.
+/// - Otherwise, with probability `noise`, we flip a random one.
+/// - Otherwise, we flip the one that breaks the fewest clauses.
+///
+/// This can never show a problem is unsatisfiable, it can only find a
+/// solution or give up after `max_flips`. Even when it gives up, the best
+/// assignment it saw is a good set of phases for a complete solver.
+pub fn search(clauses: Problem, config: &Config) -> Walk {
+    let mut rng = Random::new(config.seed);
+    let mut state = State::new(clauses, &mut rng);
+
+    let mut best = state.assignment();
+    let mut best_unsatisfied = state.unsatisfied.len();
+    let mut flips = 0;
+
+    while !state.unsatisfied.is_empty() && flips < config.max_flips {
+        let clause = state.unsatisfied[rng.below(state.unsatisfied.len())];
+        let vars: Vec<usize> = state.clauses[clause]
+            .iter()
+            .map(|l| l.variable().index() as usize)
+            .collect();
+
+        if vars.is_empty() {
+            // The empty clause, which no amount of flipping will fix.
+            break;
+        }
+
+        let least = vars.iter().map(|v| state.breaks[*v]).min().unwrap();
+        let var = if least == 0 || !rng.chance(config.noise) {
+            // Greedy, picking randomly between ties.
+            let ties: Vec<usize> = vars
+                .iter()
+                .copied()
+                .filter(|v| state.breaks[*v] == least)
+                .collect();
+            ties[rng.below(ties.len())]
+        } else {
+            vars[rng.below(vars.len())]
+        };
+
+        state.flip(var);
+        flips += 1;
+
+        if state.unsatisfied.len() < best_unsatisfied {
+            best_unsatisfied = state.unsatisfied.len();
+            best = state.assignment();
+        }
+    }
+
+    Walk {
+        assignment: best,
+        unsatisfied: best_unsatisfied,
+        flips,
+    }
+}
+
+impl LocalSearch for Config {
+    fn search(&self, clauses: Problem) -> Walk {
+        search(clauses, self)
+    }
+}
+
+// The current assignment, and everything we need to know to flip variables
+// quickly. Every clause has a weight, which is 1 unless an algorithm changes
+// it, and `make` and `breaks` are sums of weights. Other local searches share
+// this.
+pub(crate) struct State {
+    pub(crate) clauses: Vec<Vec<lit::Literal>>,
+    values: Vec<bool>,
+    // For each literal (by `code`), the clauses it appears in.
+    occurrences: Vec<Vec<usize>>,
+    // How many literals are true in each clause.
+    true_counts: Vec<usize>,
+    // The XOR of the variables of the true literals in each clause. When only
+    // one literal is true, this is its variable.
+    true_vars: Vec<usize>,
+    pub(crate) weights: Vec<u64>,
+    // The weight of the clauses that would become satisfied by flipping each
+    // variable.
+    pub(crate) make: Vec<u64>,
+    // The weight of the clauses that would become unsatisfied by flipping each
+    // variable.
+    pub(crate) breaks: Vec<u64>,
+    pub(crate) unsatisfied: Vec<usize>,
+    // Where each clause is in `unsatisfied`, if it's there.
+    positions: Vec<Option<usize>>,
+}
+
+impl State {
+    pub(crate) fn new(clauses: Problem, rng: &mut Random) -> State {
+        let vars = clauses
+            .iter()
+            .flat_map(|c| c.iter())
+            .map(|l| l.variable().index() as usize)
+            .max()
+            .unwrap_or(0);
+
+        let clauses: Vec<Vec<lit::Literal>> = clauses
+            .iter()
+            .map(|c| c.iter().copied().collect())
+            .collect();
+        let values: Vec<bool> = (0..=vars).map(|_| rng.chance(0.5)).collect();
+
+        let mut state = State {
+            occurrences: vec![vec![]; 2 * (vars + 1)],
+            true_counts: vec![0; clauses.len()],
+            true_vars: vec![0; clauses.len()],
+            weights: vec![1; clauses.len()],
+            make: vec![0; vars + 1],
+            breaks: vec![0; vars + 1],
+            unsatisfied: vec![],
+            positions: vec![None; clauses.len()],
+            values,
+            clauses,
+        };
+
+        for idx in 0..state.clauses.len() {
+            for l in state.clauses[idx].clone() {
+                state.occurrences[l.code()].push(idx);
+                if state.is_true(l) {
+                    state.true_counts[idx] += 1;
+                    state.true_vars[idx] ^= l.variable().index() as usize;
+                }
+            }
+
+            match state.true_counts[idx] {
+                0 => state.add_unsatisfied(idx),
+                1 => state.breaks[state.true_vars[idx]] += 1,
+                _ => (),
+            }
+        }
+
+        state
+    }
+
+    /// The number of variables, which are numbered from 1.
+    pub(crate) fn variables(&self) -> usize {
+        self.values.len() - 1
+    }
+
+    /// The variables in a clause.
+    pub(crate) fn clause_vars(&self, clause: usize) -> Vec<usize> {
+        self.clauses[clause]
+            .iter()
+            .map(|l| l.variable().index() as usize)
+            .collect()
+    }
+
+    /// How much better off we would be after flipping `var`.
+    pub(crate) fn score(&self, var: usize) -> i64 {
+        self.make[var] as i64 - self.breaks[var] as i64
+    }
+
+    fn is_true(&self, l: lit::Literal) -> bool {
+        self.values[l.variable().index() as usize] == l.polarity()
+    }
+
+    pub(crate) fn assignment(&self) -> Solution {
+        (1..self.values.len())
+            .map(|v| lit::Literal::new(lit::Variable::new(v as u32), self.values[v]))
+            .collect()
+    }
+
+    fn add_unsatisfied(&mut self, clause: usize) {
+        self.positions[clause] = Some(self.unsatisfied.len());
+        self.unsatisfied.push(clause);
+        for l in &self.clauses[clause] {
+            self.make[l.variable().index() as usize] += self.weights[clause];
+        }
+    }
+
+    fn remove_unsatisfied(&mut self, clause: usize) {
+        let pos = self.positions[clause].take().unwrap();
+        self.unsatisfied.swap_remove(pos);
+        if let Some(moved) = self.unsatisfied.get(pos) {
+            self.positions[*moved] = Some(pos);
+        }
+        for l in &self.clauses[clause] {
+            self.make[l.variable().index() as usize] -= self.weights[clause];
+        }
+    }
+
+    pub(crate) fn flip(&mut self, var: usize) {
+        let made_true = lit::Literal::new(lit::Variable::new(var as u32), !self.values[var]);
+        let made_false = made_true.negate();
+        self.values[var] = !self.values[var];
+
+        for idx in self.occurrences[made_true.code()].clone() {
+            match self.true_counts[idx] {
+                0 => {
+                    self.remove_unsatisfied(idx);
+                    self.breaks[var] += self.weights[idx];
+                }
+                1 => self.breaks[self.true_vars[idx]] -= self.weights[idx],
+                _ => (),
+            }
+            self.true_counts[idx] += 1;
+            self.true_vars[idx] ^= var;
+        }
+
+        for idx in self.occurrences[made_false.code()].clone() {
+            self.true_counts[idx] -= 1;
+            self.true_vars[idx] ^= var;
+            match self.true_counts[idx] {
+                0 => {
+                    self.add_unsatisfied(idx);
+                    self.breaks[var] -= self.weights[idx];
+                }
+                1 => self.breaks[self.true_vars[idx]] += self.weights[idx],
+                _ => (),
+            }
+        }
+    }
+
+    /// Change the weight of a clause, keeping `make` and `breaks` up to date.
+    pub(crate) fn set_weight(&mut self, clause: usize, weight: u64) {
+        let old = self.weights[clause];
+        self.weights[clause] = weight;
+
+        match self.true_counts[clause] {
+            0 => {
+                for l in &self.clauses[clause] {
+                    let v = l.variable().index() as usize;
+                    self.make[v] = self.make[v] - old + weight;
+                }
+            }
+            1 => {
+                let v = self.true_vars[clause];
+                self.breaks[v] = self.breaks[v] - old + weight;
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdcl;
+    use crate::local_search::tests::{planted, satisfies};
+
+    fn lit(l: i32) -> lit::Literal {
+        lit::Literal::from_dimacs(l).unwrap()
+    }
+
+    #[test]
+    fn finds_solution() {
+        let clauses = planted(100, 400, 3, 1);
+        let walk = search(&clauses, &Config::default());
+
+        assert!(walk.is_solution());
+        assert_eq!(walk.assignment.len(), 100);
+        assert!(satisfies(&walk.assignment, &clauses));
+    }
+
+    #[test]
+    fn gives_up() {
+        let clauses: Vec<cls::Clause> = vec![
+            [1, 2].iter().copied().map(lit).collect(),
+            [-1, 2].iter().copied().map(lit).collect(),
+            [1, -2].iter().copied().map(lit).collect(),
+            [-1, -2].iter().copied().map(lit).collect(),
+        ];
+        let config = Config {
+            max_flips: 100,
+            ..Config::default()
+        };
+        let walk = search(&clauses, &config);
+
+        assert!(!walk.is_solution());
+        assert_eq!(walk.unsatisfied, 1);
+        assert_eq!(walk.flips, 100);
+    }
+
+    #[test]
+    fn same_seed_same_walk() {
+        let clauses = planted(50, 150, 3, 2);
+        let a = search(
+            &clauses,
+            &Config {
+                seed: 1,
+                ..Config::default()
+            },
+        );
+        let b = search(
+            &clauses,
+            &Config {
+                seed: 1,
+                ..Config::default()
+            },
+        );
+
+        assert!(a.is_solution());
+        assert_eq!(a.assignment, b.assignment);
+        assert_eq!(a.flips, b.flips);
+    }
+
+    #[test]
+    fn phase_initializer() {
+        let clauses = planted(100, 420, 3, 3);
+        let walk = search(&clauses, &Config::default());
+        assert!(walk.is_solution());
+
+        // Starting from a solution, the complete solver has nothing to do.
+        let mut solver = cdcl::Solver::new();
+        for c in &clauses {
+            solver.add_clause(c.iter().copied());
+        }
+        for l in &walk.assignment {
+            solver.set_phase(*l);
+        }
+
+        assert_eq!(solver.solve(), cdcl::Answer::Satisfiable);
+        assert_eq!(solver.statistics().conflicts, 0);
+    }
+
+    // Recompute `make` and `breaks` from scratch.
+    fn scores(state: &State) -> (Vec<u64>, Vec<u64>) {
+        let mut make = vec![0; state.variables() + 1];
+        let mut breaks = vec![0; state.variables() + 1];
+        for (idx, c) in state.clauses.iter().enumerate() {
+            let trues: Vec<_> = c.iter().filter(|l| state.is_true(**l)).collect();
+            match trues.as_slice() {
+                [] => {
+                    for l in c {
+                        make[l.variable().index() as usize] += state.weights[idx];
+                    }
+                }
+                [l] => breaks[l.variable().index() as usize] += state.weights[idx],
+                _ => (),
+            }
+        }
+        (make, breaks)
+    }
+
+    #[test]
+    fn incremental_scores() {
+        let clauses: Vec<cls::Clause> = [
+            &[1, 2, -3][..],
+            &[-1, 3],
+            &[2, 3, 4],
+            &[-2, -4],
+            &[1, -1, 4],
+        ]
+        .iter()
+        .map(|c| c.iter().copied().map(lit).collect())
+        .collect();
+        let mut rng = Random::new(7);
+        let mut state = State::new(&clauses, &mut rng);
+
+        for i in 0..50 {
+            let var = 1 + rng.below(state.variables());
+            state.flip(var);
+            if i % 7 == 0 {
+                let clause = rng.below(clauses.len());
+                state.set_weight(clause, 1 + rng.below(5) as u64);
+            }
+            assert_eq!(scores(&state), (state.make.clone(), state.breaks.clone()));
+        }
+    }
+}
@@ -0,0 +1,227 @@
+// Copyright Sam Elliott
+// Dual-Licensed under the MIT License or the Apache License, Version 2.0.
+// See COPYRIGHT for details.
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use super::clause as c;
+use super::literal as lit;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// An XOR constraint, i.e. `x XOR y XOR z = parity`.
+///
+/// Unlike a `Clause`, negation doesn't need to be stored per-literal, as
+/// `~x XOR y` is the same as `x XOR y XOR true`.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Xor {
+    variables: BTreeSet<lit::Variable>,
+    parity: bool,
+}
+
+impl Default for Xor {
+    fn default() -> Xor {
+        Xor::new()
+    }
+}
+
+impl std::iter::FromIterator<lit::Literal> for Xor {
+    /// The XOR of the literals is `true`.
+    fn from_iter<I: IntoIterator<Item = lit::Literal>>(iter: I) -> Xor {
+        let mut xor = Xor::new();
+        xor.parity = true;
+        for l in iter {
+            xor.add_literal(l);
+        }
+        xor
+    }
+}
+
+impl Xor {
+    /// The empty XOR, which is always satisfied.
+    pub fn new() -> Xor {
+        Xor {
+            variables: BTreeSet::new(),
+            parity: false,
+        }
+    }
+
+    /// Adds `lit` into the XOR. Adding a variable twice cancels it out.
+    pub fn add_literal(&mut self, lit: lit::Literal) {
+        if !self.variables.insert(lit.variable()) {
+            self.variables.remove(&lit.variable());
+        }
+
+        if !lit.polarity() {
+            self.parity = !self.parity;
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = lit::Variable> + '_ {
+        self.variables.iter().copied()
+    }
+
+    /// What the variables have to add up to, mod 2.
+    pub fn parity(&self) -> bool {
+        self.parity
+    }
+
+    /// Is this XOR satisfied by `soln`? Variables missing from `soln` are
+    /// taken to be false.
+    pub fn is_satisfied(&self, soln: &[lit::Literal]) -> bool {
+        let sum = soln
+            .iter()
+            .filter(|l| l.polarity() && self.variables.contains(&l.variable()))
+            .count();
+        (sum % 2 == 1) == self.parity
+    }
+
+    /// The clauses that encode this XOR, which there are `2^(n-1)` of.
+    pub fn to_clauses(&self) -> Vec<c::Clause> {
+        let vars: Vec<_> = self.iter().collect();
+        if vars.is_empty() {
+            return if self.parity {
+                vec![c::Clause::new()]
+            } else {
+                vec![]
+            };
+        }
+
+        // Each clause rules out one assignment with the wrong parity.
+        (0..(1u64 << vars.len()))
+            .filter(|negated| (negated.count_ones() % 2 == 0) == self.parity)
+            .map(|negated| {
+                vars.iter()
+                    .enumerate()
+                    .map(|(i, v)| lit::Literal::new(*v, negated & (1 << i) == 0))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+// Encodings grow exponentially, so we don't look for anything longer than this.
+const MAX_RECOVERED_LEN: usize = 8;
+
+/// Find XOR constraints hidden in `clauses`.
+///
+/// An XOR over `n` variables is encoded as every one of the `2^(n-1)` clauses
+/// over those variables with the right number of negations. Returns the XORs
+/// we found, and the clauses which weren't part of any of them.
+pub fn recover(clauses: &[c::Clause]) -> (Vec<Xor>, Vec<c::Clause>) {
+    // Group clauses by the variables they mention, and then by the number of
+    // negations mod 2.
+    let mut groups: BTreeMap<(Vec<lit::Variable>, bool), BTreeMap<u64, usize>> = BTreeMap::new();
+
+    for (idx, cls) in clauses.iter().enumerate() {
+        if cls.len() < 3 || cls.len() > MAX_RECOVERED_LEN {
+            continue;
+        }
+
+        let mut lits: Vec<_> = cls.iter().copied().collect();
+        lits.sort();
+        let vars: Vec<_> = lits.iter().map(|l| l.variable()).collect();
+        if vars.windows(2).any(|w| w[0] == w[1]) {
+            // Tautologies aren't part of an encoding.
+            continue;
+        }
+
+        let negated = lits
+            .iter()
+            .enumerate()
+            .filter(|(_, l)| !l.polarity())
+            .fold(0u64, |acc, (i, _)| acc | (1 << i));
+        let odd = negated.count_ones() % 2 == 1;
+
+        groups.entry((vars, odd)).or_default().insert(negated, idx);
+    }
+
+    let mut xors = vec![];
+    let mut used = vec![false; clauses.len()];
+
+    for ((vars, odd), members) in groups {
+        if members.len() != 1 << (vars.len() - 1) {
+            continue;
+        }
+
+        // The clauses rule out every assignment with an `odd` number of true
+        // variables, as each clause rules out the assignment where all its
+        // literals are false.
+        let mut xor = Xor::new();
+        for v in vars {
+            xor.add_literal(lit::Literal::new(v, true));
+        }
+        xor.parity = !odd;
+        xors.push(xor);
+
+        for idx in members.values() {
+            used[*idx] = true;
+        }
+    }
+
+    let rest = clauses
+        .iter()
+        .zip(used)
+        .filter(|(_, used)| !used)
+        .map(|(cls, _)| cls.clone())
+        .collect();
+
+    (xors, rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lit(l: i32) -> lit::Literal {
+        lit::Literal::from_dimacs(l).unwrap()
+    }
+
+    fn xor(lits: &[i32]) -> Xor {
+        lits.iter().copied().map(lit).collect()
+    }
+
+    #[test]
+    fn negation_flips_parity() {
+        assert!(xor(&[1, 2]).parity());
+        assert!(!xor(&[1, -2]).parity());
+        assert!(xor(&[-1, -2]).parity());
+    }
+
+    #[test]
+    fn cancel_out() {
+        let x = xor(&[1, 2, 1]);
+        assert_eq!(x.iter().collect::<Vec<_>>(), vec![lit(2).variable()]);
+    }
+
+    #[test]
+    fn satisfied() {
+        let x = xor(&[1, 2, 3]);
+        assert!(x.is_satisfied(&[lit(1), lit(-2), lit(-3)]));
+        assert!(x.is_satisfied(&[lit(1), lit(2), lit(3)]));
+        assert!(!x.is_satisfied(&[lit(1), lit(2), lit(-3)]));
+    }
+
+    #[test]
+    fn round_trip() {
+        for x in [xor(&[1, 2, 3]), xor(&[-1, 2, 3, 4])].iter() {
+            let clauses = x.to_clauses();
+            assert_eq!(clauses.len(), 1 << (x.iter().count() - 1));
+
+            let (xors, rest) = recover(&clauses);
+            assert_eq!(xors.len(), 1);
+            assert_eq!(&xors[0], x);
+            assert!(rest.is_empty());
+        }
+    }
+
+    #[test]
+    fn incomplete_encoding() {
+        let mut clauses = xor(&[1, 2, 3]).to_clauses();
+        clauses.pop();
+        clauses.push([1, 4].iter().copied().map(lit).collect());
+
+        let (xors, rest) = recover(&clauses);
+        assert!(xors.is_empty());
+        assert_eq!(rest.len(), 4);
+    }
+}